@@ -1,8 +1,8 @@
 #![warn(clippy::all, clippy::pedantic)]
 
 use neutuino::prelude::*;
-use std::{io, time::Duration};
 use std::io::IsTerminal;
+use std::{io, time::Duration};
 
 fn print_line_style_reset(string: &str) {
     println!("{}{}{}", string, STYLE_RESET, move_cursor_to_column(0));
@@ -14,13 +14,20 @@ fn main() -> io::Result<()> {
     let all_styles = format!("{STYLE_BOLD}{STYLE_ITALIC}{STYLE_UNDERLINE}");
 
     enable_ansi()?;
+    // Restores the terminal even if the handlers below don't get dropped
+    install_restore_hook()?;
     let _raw_terminal = RawModeHandler::new()?;
+    let _mouse_capture = MouseCaptureHandler::new()?;
+    let _bracketed_paste = BracketedPasteHandler::new()?;
+    let _focus_reporting = FocusReportingHandler::new()?;
+    let _keyboard_enhancement =
+        KeyboardEnhancementHandler::new(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)?;
 
     println!("q to quit{}", move_cursor_to_column(0));
     let next = |x: usize| (x + 1) % COLORS_FG.len();
 
     let terminal_size = get_terminal_size()?;
-    let terminal_size_str = format!("{:?}", terminal_size);
+    let terminal_size_str = format!("{terminal_size:?}");
     print!("{}", set_window_title(terminal_size_str).unwrap());
 
     let mut counter = 0;
@@ -38,7 +45,9 @@ fn main() -> io::Result<()> {
             COLORS_BG[next(counter)]
         ));
         // q to quit
-        if input == Event::Key(KeyEvent::Char('q')) {
+        if let Event::Key(key) = &input
+            && key.matches(KeyCode::Char('q'), Modifiers::NONE)
+        {
             break;
         }
         counter = next(counter);
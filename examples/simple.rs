@@ -21,6 +21,7 @@ fn main() -> io::Result<()> {
 
     let string = "Hello, World!";
 
+    #[allow(clippy::cast_possible_truncation)]
     let adjusted_middle = (middle.0 - ((string.len() / 2) as u16), middle.1);
 
     print!(
@@ -4,7 +4,13 @@
 //!
 //! For these to work on Windows you need to run the `enable_ansi` function in the os module
 
+use crate::base64;
+use crate::input::{Event, KeyboardEnhancementFlags, ModeSetting};
+use crate::os::ColorSupport;
+use std::fmt;
 use std::io::{self, Write};
+use std::ops::{BitOr, BitOrAssign};
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
 
 /// Sets the terminal to an arbitrary 12-bit/truecolor color in the foreground when printed
 #[must_use]
@@ -28,6 +34,60 @@ pub fn set_window_title<T: Into<String>>(title: T) -> Option<String> {
     Some(format!("\x1b]0;{title}\x1b\x5c"))
 }
 
+/// Pushes keyboard enhancement flags onto the terminal's stack when printed
+///
+/// Terminals that don't support the kitty keyboard protocol will ignore this
+/// and keep reporting keys the legacy way
+#[must_use]
+pub fn push_keyboard_enhancement_flags(flags: KeyboardEnhancementFlags) -> String {
+    format!("\x1b[>{}u", flags.bits())
+}
+
+/// Pops {num} entries off the terminal's keyboard enhancement flags stack when printed
+#[must_use]
+pub fn pop_keyboard_enhancement_flags(num: u16) -> String {
+    format!("\x1b[<{num}u")
+}
+
+/// Queries the setting of DEC private mode {mode} when printed
+///
+/// The terminal will reply with `Event::ModeReport`,
+/// terminals that don't support querying modes won't reply at all
+#[must_use]
+pub fn mode_query(mode: u16) -> String {
+    format!("\x1b[?{mode}$p")
+}
+
+/// Queries color {index} of the 256 color palette when printed
+///
+/// The terminal will reply with `Event::PaletteColor`, see `os::get_palette_color`
+#[must_use]
+pub fn palette_color_query(index: u8) -> String {
+    format!("\x1b]4;{index};?\x1b\x5c")
+}
+
+/// Sets the contents of {selection} to {text} when printed (OSC 52)
+///
+/// Works over SSH as the text is sent to the local terminal,
+/// some terminals ignore this or limit how long the text can be
+#[must_use]
+pub fn set_clipboard(selection: ClipboardSelection, text: &str) -> String {
+    format!(
+        "\x1b]52;{};{}\x1b\x5c",
+        selection.parameter(),
+        base64::encode(text.as_bytes())
+    )
+}
+
+/// Queries the contents of {selection} when printed (OSC 52)
+///
+/// The terminal will reply with `Event::Clipboard`, see `os::get_clipboard`.
+/// Most terminals don't allow reading the clipboard by default and won't reply at all
+#[must_use]
+pub fn clipboard_query(selection: ClipboardSelection) -> String {
+    format!("\x1b]52;{};?\x1b\x5c", selection.parameter())
+}
+
 /// Moves the cursor up {num} characters when printed
 #[must_use]
 pub fn move_cursor_up(num: u16) -> String {
@@ -84,6 +144,15 @@ pub fn move_cursor_to_position(column: u16, line: u16) -> String {
 pub const CURSOR_POSITION_SAVE: &str = "\x1b7";
 /// Restores the saved cursor position
 pub const CURSOR_POSITION_RESTORE: &str = "\x1b8";
+/// Hides the cursor
+pub const CURSOR_HIDE: &str = "\x1b[?25l";
+/// Shows the cursor
+pub const CURSOR_SHOW: &str = "\x1b[?25h";
+
+/// Queries the cursor position
+///
+/// The terminal will reply with `Event::CursorPosition`, see `os::get_cursor_position`
+pub const CURSOR_POSITION_QUERY: &str = "\x1b[6n";
 
 /// Enters the alternate screen
 ///
@@ -94,6 +163,79 @@ pub const ALT_SCREEN_ENTER: &str = "\x1b[?1049h";
 /// The alternate screen is a blank screen that won't interrupt the main screen (e.g. vi)
 pub const ALT_SCREEN_EXIT: &str = "\x1b[?1049l";
 
+/// Enables mouse capture
+///
+/// The terminal will report clicks, drags, movement, and scrolling as `Event::Mouse`
+///
+/// Requests SGR encoded reports with the urxvt encoding as a fallback,
+/// terminals supporting neither will use the legacy X10 encoding
+pub const MOUSE_CAPTURE_ENABLE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1003h\x1b[?1015h\x1b[?1006h";
+/// Disables mouse capture
+pub const MOUSE_CAPTURE_DISABLE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1003l\x1b[?1002l\x1b[?1000l";
+
+/// Enables bracketed paste
+///
+/// Pasted text will be reported as a single `Event::Paste` instead of individual key presses
+pub const BRACKETED_PASTE_ENABLE: &str = "\x1b[?2004h";
+/// Disables bracketed paste
+pub const BRACKETED_PASTE_DISABLE: &str = "\x1b[?2004l";
+
+/// Enables focus reporting
+///
+/// The terminal will report its window gaining or losing focus
+/// as `Event::FocusGained` and `Event::FocusLost`
+pub const FOCUS_REPORTING_ENABLE: &str = "\x1b[?1004h";
+/// Disables focus reporting
+pub const FOCUS_REPORTING_DISABLE: &str = "\x1b[?1004l";
+
+/// Queries the current keyboard enhancement flags
+///
+/// The terminal will reply with `Event::KeyboardEnhancementFlags`,
+/// terminals that don't support the kitty keyboard protocol won't reply at all
+pub const KEYBOARD_ENHANCEMENT_QUERY: &str = "\x1b[?u";
+
+/// Queries the terminal's primary device attributes (DA1)
+///
+/// The terminal will reply with `Event::PrimaryDeviceAttributes`,
+/// practically every terminal replies to this
+pub const PRIMARY_DEVICE_ATTRIBUTES_QUERY: &str = "\x1b[c";
+/// Queries the terminal's secondary device attributes (DA2)
+///
+/// The terminal will reply with `Event::SecondaryDeviceAttributes`
+pub const SECONDARY_DEVICE_ATTRIBUTES_QUERY: &str = "\x1b[>c";
+/// Queries the terminal's name and version (XTVERSION)
+///
+/// The terminal will reply with `Event::TerminalVersion`,
+/// terminals that don't support it won't reply at all
+pub const TERMINAL_VERSION_QUERY: &str = "\x1b[>q";
+
+/// Queries the default foreground color
+///
+/// The terminal will reply with `Event::ForegroundColor`, see `os::get_foreground_color`
+pub const FOREGROUND_COLOR_QUERY: &str = "\x1b]10;?\x1b\x5c";
+/// Queries the default background color
+///
+/// The terminal will reply with `Event::BackgroundColor`, see `os::get_background_color`
+pub const BACKGROUND_COLOR_QUERY: &str = "\x1b]11;?\x1b\x5c";
+
+/// Queries the size of the terminal's text area in pixels
+///
+/// The terminal will reply with `Event::WindowPixelSize`, see `os::get_terminal_size_detailed`
+pub const WINDOW_PIXEL_SIZE_QUERY: &str = "\x1b[14t";
+/// Queries the size of a character cell in pixels
+///
+/// The terminal will reply with `Event::CellPixelSize`, see `os::get_terminal_size_detailed`
+pub const CELL_PIXEL_SIZE_QUERY: &str = "\x1b[16t";
+
+/// Begins a synchronized update
+///
+/// The terminal holds off on drawing until `SYNCHRONIZED_UPDATE_END`,
+/// which stops partially drawn frames from flickering.
+/// Terminals without `Capabilities::synchronized_output` ignore this
+pub const SYNCHRONIZED_UPDATE_BEGIN: &str = "\x1b[?2026h";
+/// Ends a synchronized update, drawing everything since `SYNCHRONIZED_UPDATE_BEGIN`
+pub const SYNCHRONIZED_UPDATE_END: &str = "\x1b[?2026l";
+
 /// Sets the cursor shape to the user-specified default
 pub const SHAPE_RESET: &str = "\x1b[0q";
 /// Sets the cursor shape to a blinking block
@@ -251,10 +393,532 @@ pub const COLORS: [(&str, &str); 9] = [
     (COLOR_DEFAULT_FG, COLOR_DEFAULT_BG),
 ];
 
+/// A color that text, its background, or its underline can be set to
+///
+/// ```
+/// use neutuino::ansi::{Color, STYLE_RESET};
+///
+/// println!("{}{}Warning{STYLE_RESET}", Color::BRIGHT_YELLOW.fg(), Color::Indexed(52).bg());
+/// ```
+///
+/// Displays as `fg`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Color {
+    /// The terminal's default color
+    #[default]
+    Reset,
+    /// One of the 16 standard colors, 0 to 7 are the normal colors and 8 to 15 their
+    /// bright versions in the order:
+    /// Black, Red, Green, Yellow, Blue, Magenta, Cyan, White
+    ///
+    /// Only the lowest 4 bits are used. The terminal's theme decides what these look like
+    Ansi(u8),
+    /// A color in the 256 color palette, 0 to 15 are the same as `Ansi`,
+    /// 16 to 231 are a 6x6x6 color cube, and 232 to 255 are a grayscale ramp
+    Indexed(u8),
+    /// A 24-bit/truecolor color in (red, green, blue) format
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Black
+    pub const BLACK: Self = Self::Ansi(0);
+    /// Red
+    pub const RED: Self = Self::Ansi(1);
+    /// Green
+    pub const GREEN: Self = Self::Ansi(2);
+    /// Yellow
+    pub const YELLOW: Self = Self::Ansi(3);
+    /// Blue
+    pub const BLUE: Self = Self::Ansi(4);
+    /// Magenta
+    pub const MAGENTA: Self = Self::Ansi(5);
+    /// Cyan
+    pub const CYAN: Self = Self::Ansi(6);
+    /// White, often light gray
+    pub const WHITE: Self = Self::Ansi(7);
+    /// Bright black, often dark gray
+    pub const BRIGHT_BLACK: Self = Self::Ansi(8);
+    /// Bright red
+    pub const BRIGHT_RED: Self = Self::Ansi(9);
+    /// Bright green
+    pub const BRIGHT_GREEN: Self = Self::Ansi(10);
+    /// Bright yellow
+    pub const BRIGHT_YELLOW: Self = Self::Ansi(11);
+    /// Bright blue
+    pub const BRIGHT_BLUE: Self = Self::Ansi(12);
+    /// Bright magenta
+    pub const BRIGHT_MAGENTA: Self = Self::Ansi(13);
+    /// Bright cyan
+    pub const BRIGHT_CYAN: Self = Self::Ansi(14);
+    /// Bright white
+    pub const BRIGHT_WHITE: Self = Self::Ansi(15);
+
+    /// Sets the foreground to this color when printed
+    #[must_use]
+    pub fn fg(self) -> String {
+        format!("\x1b[{}m", self.parameters(30))
+    }
+    /// Sets the background to this color when printed
+    #[must_use]
+    pub fn bg(self) -> String {
+        format!("\x1b[{}m", self.parameters(40))
+    }
+    /// Sets the underline to this color when printed
+    ///
+    /// This is less commonly supported than other colors,
+    /// terminals that don't support it keep underlines the same color as the text
+    #[must_use]
+    pub fn underline(self) -> String {
+        format!("\x1b[{}m", self.parameters(50))
+    }
+
+    /// Converts this color to the closest one the terminal can show with {support}, see
+    /// `os::get_color_support`
+    ///
+    /// Truecolor colors become the closest color of the 256 color palette's color cube and
+    /// grayscale ramp, or of the 16 standard colors (as they look in xterm by default).
+    /// Colors become `Reset` with `ColorSupport::None`, though no colors should be printed at all
+    #[must_use]
+    pub fn downsample(self, support: ColorSupport) -> Self {
+        match (self, support) {
+            (_, ColorSupport::None) => Self::Reset,
+            (Self::Indexed(index), ColorSupport::Ansi16) if index < 16 => Self::Ansi(index),
+            (Self::Indexed(index), ColorSupport::Ansi16) => {
+                let (red, green, blue) = indexed_rgb(index);
+                Self::Ansi(closest_ansi(red, green, blue))
+            }
+            (Self::Rgb(red, green, blue), ColorSupport::Ansi16) => {
+                Self::Ansi(closest_ansi(red, green, blue))
+            }
+            (Self::Rgb(red, green, blue), ColorSupport::Ansi256) => {
+                Self::Indexed(closest_indexed(red, green, blue))
+            }
+            (color, _) => color,
+        }
+    }
+
+    /// Gets the SGR parameters that set this color, {base} is 30 for the foreground,
+    /// 40 for the background, and 50 for the underline
+    fn parameters(self, base: u8) -> String {
+        match self {
+            Self::Reset => (base + 9).to_string(),
+            // Underlines only have the extended form
+            Self::Ansi(index) if base == 50 => format!("58;5;{}", index & 15),
+            Self::Ansi(index) if index & 8 == 0 => (base + (index & 7)).to_string(),
+            Self::Ansi(index) => (base + 60 + (index & 7)).to_string(),
+            Self::Indexed(index) => format!("{};5;{index}", base + 8),
+            Self::Rgb(red, green, blue) => format!("{};2;{red};{green};{blue}", base + 8),
+        }
+    }
+}
+
+/// The 16 standard colors as xterm shows them by default
+const ANSI_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The levels of each channel in the 256 color palette's color cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Gets the (red, green, blue) of color {index} of the 256 color palette
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..16 => ANSI_RGB[usize::from(index)],
+        16..232 => {
+            let cube = usize::from(index - 16);
+            (
+                CUBE_LEVELS[cube / 36],
+                CUBE_LEVELS[cube / 6 % 6],
+                CUBE_LEVELS[cube % 6],
+            )
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// Gets how different two colors look, using the "redmean" approximation
+/// which weighs the channels by how sensitive eyes are to them
+fn color_distance((red1, green1, blue1): (u8, u8, u8), (red2, green2, blue2): (u8, u8, u8)) -> u32 {
+    let red_mean = u32::from(red1.midpoint(red2));
+    let red = u32::from(red1.abs_diff(red2));
+    let green = u32::from(green1.abs_diff(green2));
+    let blue = u32::from(blue1.abs_diff(blue2));
+    (((512 + red_mean) * red * red) >> 8)
+        + 4 * green * green
+        + (((767 - red_mean) * blue * blue) >> 8)
+}
+
+/// Gets the index of the standard color closest to {red}, {green}, {blue}
+fn closest_ansi(red: u8, green: u8, blue: u8) -> u8 {
+    (0..16)
+        .min_by_key(|&index| color_distance(ANSI_RGB[usize::from(index)], (red, green, blue)))
+        .unwrap_or(0)
+}
+
+/// Gets the index of the color closest to {red}, {green}, {blue} in the 256 color palette's
+/// color cube and grayscale ramp
+///
+/// The 16 standard colors are skipped as terminal themes often change them
+fn closest_indexed(red: u8, green: u8, blue: u8) -> u8 {
+    let closest_level = |value: u8| {
+        (0..6)
+            .min_by_key(|&level| CUBE_LEVELS[usize::from(level)].abs_diff(value))
+            .unwrap_or(0)
+    };
+    let cube = 16 + 36 * closest_level(red) + 6 * closest_level(green) + closest_level(blue);
+
+    let average = (u16::from(red) + u16::from(green) + u16::from(blue)) / 3;
+    let gray = 232
+        + u8::try_from(average.saturating_sub(3) / 10)
+            .unwrap_or(0)
+            .min(23);
+
+    [cube, gray]
+        .into_iter()
+        .min_by_key(|&index| color_distance(indexed_rgb(index), (red, green, blue)))
+        .unwrap_or(cube)
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\x1b[{}m", self.parameters(30))
+    }
+}
+
+/// A set of text attributes, e.g. bold or italic
+///
+/// Combine attributes with `|`, e.g. `Attributes::BOLD | Attributes::ITALIC`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Attributes(u8);
+
+impl Attributes {
+    /// No attributes
+    pub const NONE: Self = Self(0);
+    /// Bold, see `STYLE_BOLD`
+    pub const BOLD: Self = Self(1);
+    /// Dim, see `STYLE_DIM`
+    pub const DIM: Self = Self(1 << 1);
+    /// Italic, see `STYLE_ITALIC`
+    pub const ITALIC: Self = Self(1 << 2);
+    /// Underlined, see `STYLE_UNDERLINE`
+    pub const UNDERLINE: Self = Self(1 << 3);
+    /// Blinking, see `STYLE_BLINKING`
+    pub const BLINKING: Self = Self(1 << 4);
+    /// Reversed, see `STYLE_REVERSE`
+    pub const REVERSE: Self = Self(1 << 5);
+    /// Hidden, see `STYLE_HIDDEN`
+    pub const HIDDEN: Self = Self(1 << 6);
+    /// Struckthrough, see `STYLE_STRIKETHROUGH`
+    pub const STRIKETHROUGH: Self = Self(1 << 7);
+
+    /// Every attribute with the SGR parameters that set and reset it
+    const PARAMETERS: [(Self, u8, u8); 8] = [
+        (Self::BOLD, 1, 22),
+        (Self::DIM, 2, 22),
+        (Self::ITALIC, 3, 23),
+        (Self::UNDERLINE, 4, 24),
+        (Self::BLINKING, 5, 25),
+        (Self::REVERSE, 7, 27),
+        (Self::HIDDEN, 8, 28),
+        (Self::STRIKETHROUGH, 9, 29),
+    ];
+
+    /// Gets the SGR parameters that set every attribute in the set
+    fn parameters(self) -> impl Iterator<Item = String> {
+        Self::PARAMETERS
+            .into_iter()
+            .filter(move |&(attribute, _, _)| self.contains(attribute))
+            .map(|(_, set, _)| set.to_string())
+    }
+
+    /// Creates a set of attributes from its raw bits
+    #[must_use]
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+    /// Gets the raw bits of the set
+    #[must_use]
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+    /// Gets the set with every attribute in both sets
+    ///
+    /// Same as `|` but usable in constants
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+    /// Gets if no attributes are in the set
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    /// Gets if every attribute in `other` is also in the set
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    /// Adds every attribute in `other` to the set
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+    /// Removes every attribute in `other` from the set
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for Attributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Attributes {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.insert(rhs);
+    }
+}
+
+/// The colors and attributes of text
+///
+/// ```
+/// use neutuino::ansi::{Attributes, Color, Style};
+///
+/// let title = Style::new().fg(Color::BRIGHT_WHITE).attrs(Attributes::BOLD);
+/// let body = Style::new();
+/// print!("{title}Title{}", title.transition(&body));
+/// ```
+///
+/// Displays as the codes that reset the terminal's style and then set this one,
+/// use `transition` to change from one style to another with as few codes as possible
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Style {
+    /// The color of the text
+    pub fg: Color,
+    /// The color behind the text
+    pub bg: Color,
+    /// The color of underlines, see `Color::underline`
+    pub underline_color: Color,
+    /// The attributes of the text
+    pub attrs: Attributes,
+}
+
+impl Style {
+    /// Creates the terminal's default style, with default colors and no attributes
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            fg: Color::Reset,
+            bg: Color::Reset,
+            underline_color: Color::Reset,
+            attrs: Attributes::NONE,
+        }
+    }
+    /// Sets the color of the text
+    #[must_use]
+    pub const fn fg(mut self, color: Color) -> Self {
+        self.fg = color;
+        self
+    }
+    /// Sets the color behind the text
+    #[must_use]
+    pub const fn bg(mut self, color: Color) -> Self {
+        self.bg = color;
+        self
+    }
+    /// Sets the color of underlines
+    #[must_use]
+    pub const fn underline_color(mut self, color: Color) -> Self {
+        self.underline_color = color;
+        self
+    }
+    /// Sets the attributes of the text
+    #[must_use]
+    pub const fn attrs(mut self, attrs: Attributes) -> Self {
+        self.attrs = attrs;
+        self
+    }
+
+    /// Changes from this style to {to} when printed, using as few codes as possible
+    ///
+    /// Assumes the terminal is currently in this style. Bold and dim share a reset,
+    /// so removing one of them sets the other again if {to} has it
+    #[must_use]
+    pub fn transition(&self, to: &Self) -> String {
+        if self == to {
+            return String::new();
+        }
+        let mut parameters = Vec::new();
+        let removed = Attributes(self.attrs.0 & !to.attrs.0);
+        let mut added = Attributes(to.attrs.0 & !self.attrs.0);
+        let bold_or_dim = Attributes::BOLD | Attributes::DIM;
+        if removed.0 & bold_or_dim.0 != 0 {
+            added.insert(Attributes(to.attrs.0 & bold_or_dim.0));
+        }
+        let mut reset_bold_or_dim = false;
+        for (attribute, _, reset) in Attributes::PARAMETERS {
+            if removed.contains(attribute) && !(reset == 22 && reset_bold_or_dim) {
+                parameters.push(reset.to_string());
+                reset_bold_or_dim |= reset == 22;
+            }
+        }
+        parameters.extend(added.parameters());
+        for (old, new, base) in [
+            (self.fg, to.fg, 30),
+            (self.bg, to.bg, 40),
+            (self.underline_color, to.underline_color, 50),
+        ] {
+            if old != new {
+                parameters.push(new.parameters(base));
+            }
+        }
+
+        let changes = parameters.join(";");
+        let full = to.parameters();
+        // Resetting everything is shorter when most of the style changes
+        let shortest = if full.len() < changes.len() {
+            full
+        } else {
+            changes
+        };
+        format!("\x1b[{shortest}m")
+    }
+
+    /// Gets the SGR parameters that reset the terminal's style and then set this one
+    fn parameters(&self) -> String {
+        let mut parameters = vec!["0".to_string()];
+        parameters.extend(self.attrs.parameters());
+        for (color, base) in [(self.fg, 30), (self.bg, 40), (self.underline_color, 50)] {
+            if color != Color::Reset {
+                parameters.push(color.parameters(base));
+            }
+        }
+        parameters.join(";")
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\x1b[{}m", self.parameters())
+    }
+}
+
+/// Modes enabled by the handlers, in the order they were enabled
+///
+/// Used to disable and re-enable them around suspending the process, see `os::suspend`
+static ACTIVE_MODES: Mutex<Vec<ActiveMode>> = Mutex::new(Vec::new());
+
+/// A mode enabled by one of the handlers
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ActiveMode {
+    AltScreen,
+    MouseCapture,
+    BracketedPaste,
+    FocusReporting,
+    KeyboardEnhancement(KeyboardEnhancementFlags),
+}
+
+impl ActiveMode {
+    fn enable_code(self) -> String {
+        match self {
+            Self::AltScreen => ALT_SCREEN_ENTER.to_string(),
+            Self::MouseCapture => MOUSE_CAPTURE_ENABLE.to_string(),
+            Self::BracketedPaste => BRACKETED_PASTE_ENABLE.to_string(),
+            Self::FocusReporting => FOCUS_REPORTING_ENABLE.to_string(),
+            Self::KeyboardEnhancement(flags) => push_keyboard_enhancement_flags(flags),
+        }
+    }
+    fn disable_code(self) -> &'static str {
+        match self {
+            Self::AltScreen => ALT_SCREEN_EXIT,
+            Self::MouseCapture => MOUSE_CAPTURE_DISABLE,
+            Self::BracketedPaste => BRACKETED_PASTE_DISABLE,
+            Self::FocusReporting => FOCUS_REPORTING_DISABLE,
+            // Same as `pop_keyboard_enhancement_flags(1)`, without allocating
+            Self::KeyboardEnhancement(_) => "\x1b[<1u",
+        }
+    }
+}
+
+fn lock_active_modes() -> MutexGuard<'static, Vec<ActiveMode>> {
+    ACTIVE_MODES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn set_mode_active(mode: ActiveMode, active: bool) {
+    let mut modes = lock_active_modes();
+    if active {
+        modes.push(mode);
+    } else if let Some(index) = modes.iter().rposition(|&active| active == mode) {
+        modes.remove(index);
+    }
+}
+
+/// Gets if {mode} is enabled, it isn't anymore once `restore_active_modes` disabled it
+fn is_mode_active(mode: ActiveMode) -> bool {
+    lock_active_modes().contains(&mode)
+}
+
+/// Gets the codes that disable every mode enabled by the handlers, most recent first
+pub(crate) fn disable_active_modes() -> String {
+    lock_active_modes()
+        .iter()
+        .rev()
+        .map(|mode| mode.disable_code())
+        .collect()
+}
+
+/// Passes the codes that disable every mode enabled by the handlers to {write},
+/// and forgets about them so the handlers don't disable them again
+///
+/// Doesn't allocate or wait for other threads so it can be used in signal handlers,
+/// does nothing if the modes are being changed at the same time
+pub(crate) fn restore_active_modes(mut write: impl FnMut(&str)) {
+    let mut modes = match ACTIVE_MODES.try_lock() {
+        Ok(modes) => modes,
+        Err(TryLockError::Poisoned(error)) => error.into_inner(),
+        Err(TryLockError::WouldBlock) => return,
+    };
+    for mode in modes.iter().rev() {
+        write(mode.disable_code());
+    }
+    modes.clear();
+}
+
+/// Gets the codes that enable every mode enabled by the handlers again
+pub(crate) fn enable_active_modes() -> String {
+    lock_active_modes()
+        .iter()
+        .map(|mode| mode.enable_code())
+        .collect()
+}
+
 /// Struct that prints `ALT_SCREEN_ENTER` on construction
 /// and `ALT_SCREEN_EXIT` on destruction
 ///
-/// Prefered over function as it prints `ALT_SCREEN_EXIT` on panic
+/// Prefered over function as it prints `ALT_SCREEN_EXIT` on panic,
+/// see `os::install_restore_hook` for when it can't be dropped
 pub struct AltScreenHandler {
     enabled: bool,
 }
@@ -268,6 +932,7 @@ impl AltScreenHandler {
     pub fn new() -> io::Result<Self> {
         print!("{ALT_SCREEN_ENTER}");
         io::stdout().flush()?;
+        set_mode_active(ActiveMode::AltScreen, true);
         Ok(Self { enabled: true })
     }
     /// Enables raw mode
@@ -303,11 +968,12 @@ impl AltScreenHandler {
         }
         if alt {
             print!("{ALT_SCREEN_ENTER}");
-        } else {
+        } else if is_mode_active(ActiveMode::AltScreen) {
             print!("{ALT_SCREEN_EXIT}");
         }
         io::stdout().flush()?;
         self.enabled = alt;
+        set_mode_active(ActiveMode::AltScreen, alt);
         Ok(())
     }
     /// Gets if the alt screen is enabled
@@ -319,6 +985,556 @@ impl AltScreenHandler {
 
 impl Drop for AltScreenHandler {
     fn drop(&mut self) {
-        self.disable().expect("Failed to disable alternate screen");
+        // Panicking here would abort if it is dropped while unwinding
+        let _ = self.disable();
+    }
+}
+
+/// Struct that prints `MOUSE_CAPTURE_ENABLE` on construction
+/// and `MOUSE_CAPTURE_DISABLE` on destruction
+///
+/// Prefered over function as it prints `MOUSE_CAPTURE_DISABLE` on panic
+pub struct MouseCaptureHandler {
+    enabled: bool,
+}
+
+impl MouseCaptureHandler {
+    /// Creates a new instance and enables mouse capture
+    ///
+    /// # Errors
+    ///
+    /// If it fails to print or flush the output
+    pub fn new() -> io::Result<Self> {
+        print!("{MOUSE_CAPTURE_ENABLE}");
+        io::stdout().flush()?;
+        set_mode_active(ActiveMode::MouseCapture, true);
+        Ok(Self { enabled: true })
+    }
+    /// Enables mouse capture
+    ///
+    /// # Errors
+    ///
+    /// Never errors if mouse capture is already enabled
+    ///
+    /// If it fails to print or flush the output
+    pub fn enable(&mut self) -> io::Result<()> {
+        self.set(true)
+    }
+    /// Disables mouse capture
+    ///
+    /// # Errors
+    ///
+    /// Never errors if mouse capture is already disabled
+    ///
+    /// If it fails to print or flush the output
+    pub fn disable(&mut self) -> io::Result<()> {
+        self.set(false)
+    }
+    /// Sets mouse capture
+    ///
+    /// # Errors
+    ///
+    /// Never errors if mouse capture is in the same state as the boolean
+    ///
+    /// If it fails to print or flush the output
+    pub fn set(&mut self, capture: bool) -> io::Result<()> {
+        if self.enabled == capture {
+            return Ok(());
+        }
+        if capture {
+            print!("{MOUSE_CAPTURE_ENABLE}");
+        } else if is_mode_active(ActiveMode::MouseCapture) {
+            print!("{MOUSE_CAPTURE_DISABLE}");
+        }
+        io::stdout().flush()?;
+        self.enabled = capture;
+        set_mode_active(ActiveMode::MouseCapture, capture);
+        Ok(())
+    }
+    /// Gets if mouse capture is enabled
+    #[must_use]
+    pub fn get(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl Drop for MouseCaptureHandler {
+    fn drop(&mut self) {
+        let _ = self.disable();
+    }
+}
+
+/// Struct that prints `BRACKETED_PASTE_ENABLE` on construction
+/// and `BRACKETED_PASTE_DISABLE` on destruction
+///
+/// Prefered over function as it prints `BRACKETED_PASTE_DISABLE` on panic
+pub struct BracketedPasteHandler {
+    enabled: bool,
+}
+
+impl BracketedPasteHandler {
+    /// Creates a new instance and enables bracketed paste
+    ///
+    /// # Errors
+    ///
+    /// If it fails to print or flush the output
+    pub fn new() -> io::Result<Self> {
+        print!("{BRACKETED_PASTE_ENABLE}");
+        io::stdout().flush()?;
+        set_mode_active(ActiveMode::BracketedPaste, true);
+        Ok(Self { enabled: true })
+    }
+    /// Enables bracketed paste
+    ///
+    /// # Errors
+    ///
+    /// Never errors if bracketed paste is already enabled
+    ///
+    /// If it fails to print or flush the output
+    pub fn enable(&mut self) -> io::Result<()> {
+        self.set(true)
+    }
+    /// Disables bracketed paste
+    ///
+    /// # Errors
+    ///
+    /// Never errors if bracketed paste is already disabled
+    ///
+    /// If it fails to print or flush the output
+    pub fn disable(&mut self) -> io::Result<()> {
+        self.set(false)
+    }
+    /// Sets bracketed paste
+    ///
+    /// # Errors
+    ///
+    /// Never errors if bracketed paste is in the same state as the boolean
+    ///
+    /// If it fails to print or flush the output
+    pub fn set(&mut self, paste: bool) -> io::Result<()> {
+        if self.enabled == paste {
+            return Ok(());
+        }
+        if paste {
+            print!("{BRACKETED_PASTE_ENABLE}");
+        } else if is_mode_active(ActiveMode::BracketedPaste) {
+            print!("{BRACKETED_PASTE_DISABLE}");
+        }
+        io::stdout().flush()?;
+        self.enabled = paste;
+        set_mode_active(ActiveMode::BracketedPaste, paste);
+        Ok(())
+    }
+    /// Gets if bracketed paste is enabled
+    #[must_use]
+    pub fn get(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl Drop for BracketedPasteHandler {
+    fn drop(&mut self) {
+        let _ = self.disable();
+    }
+}
+
+/// Struct that prints `FOCUS_REPORTING_ENABLE` on construction
+/// and `FOCUS_REPORTING_DISABLE` on destruction
+///
+/// Prefered over function as it prints `FOCUS_REPORTING_DISABLE` on panic
+pub struct FocusReportingHandler {
+    enabled: bool,
+}
+
+impl FocusReportingHandler {
+    /// Creates a new instance and enables focus reporting
+    ///
+    /// # Errors
+    ///
+    /// If it fails to print or flush the output
+    pub fn new() -> io::Result<Self> {
+        print!("{FOCUS_REPORTING_ENABLE}");
+        io::stdout().flush()?;
+        set_mode_active(ActiveMode::FocusReporting, true);
+        Ok(Self { enabled: true })
+    }
+    /// Enables focus reporting
+    ///
+    /// # Errors
+    ///
+    /// Never errors if focus reporting is already enabled
+    ///
+    /// If it fails to print or flush the output
+    pub fn enable(&mut self) -> io::Result<()> {
+        self.set(true)
+    }
+    /// Disables focus reporting
+    ///
+    /// # Errors
+    ///
+    /// Never errors if focus reporting is already disabled
+    ///
+    /// If it fails to print or flush the output
+    pub fn disable(&mut self) -> io::Result<()> {
+        self.set(false)
+    }
+    /// Sets focus reporting
+    ///
+    /// # Errors
+    ///
+    /// Never errors if focus reporting is in the same state as the boolean
+    ///
+    /// If it fails to print or flush the output
+    pub fn set(&mut self, focus: bool) -> io::Result<()> {
+        if self.enabled == focus {
+            return Ok(());
+        }
+        if focus {
+            print!("{FOCUS_REPORTING_ENABLE}");
+        } else if is_mode_active(ActiveMode::FocusReporting) {
+            print!("{FOCUS_REPORTING_DISABLE}");
+        }
+        io::stdout().flush()?;
+        self.enabled = focus;
+        set_mode_active(ActiveMode::FocusReporting, focus);
+        Ok(())
+    }
+    /// Gets if focus reporting is enabled
+    #[must_use]
+    pub fn get(&self) -> bool {
+        self.enabled
+    }
+}
+
+impl Drop for FocusReportingHandler {
+    fn drop(&mut self) {
+        let _ = self.disable();
+    }
+}
+
+/// Struct that pushes keyboard enhancement flags on construction
+/// and pops them on destruction
+///
+/// Prefered over function as it pops the flags on panic
+pub struct KeyboardEnhancementHandler {
+    flags: KeyboardEnhancementFlags,
+    enabled: bool,
+}
+
+impl KeyboardEnhancementHandler {
+    /// Creates a new instance and pushes the keyboard enhancement flags
+    ///
+    /// # Errors
+    ///
+    /// If it fails to print or flush the output
+    pub fn new(flags: KeyboardEnhancementFlags) -> io::Result<Self> {
+        print!("{}", push_keyboard_enhancement_flags(flags));
+        io::stdout().flush()?;
+        set_mode_active(ActiveMode::KeyboardEnhancement(flags), true);
+        Ok(Self {
+            flags,
+            enabled: true,
+        })
+    }
+    /// Pushes the keyboard enhancement flags
+    ///
+    /// # Errors
+    ///
+    /// Never errors if the flags are already pushed
+    ///
+    /// If it fails to print or flush the output
+    pub fn enable(&mut self) -> io::Result<()> {
+        self.set(true)
+    }
+    /// Pops the keyboard enhancement flags
+    ///
+    /// # Errors
+    ///
+    /// Never errors if the flags are already popped
+    ///
+    /// If it fails to print or flush the output
+    pub fn disable(&mut self) -> io::Result<()> {
+        self.set(false)
+    }
+    /// Sets if the keyboard enhancement flags are pushed
+    ///
+    /// # Errors
+    ///
+    /// Never errors if the flags are in the same state as the boolean
+    ///
+    /// If it fails to print or flush the output
+    pub fn set(&mut self, enhanced: bool) -> io::Result<()> {
+        if self.enabled == enhanced {
+            return Ok(());
+        }
+        if enhanced {
+            print!("{}", push_keyboard_enhancement_flags(self.flags));
+        } else if is_mode_active(ActiveMode::KeyboardEnhancement(self.flags)) {
+            print!("{}", pop_keyboard_enhancement_flags(1));
+        }
+        io::stdout().flush()?;
+        self.enabled = enhanced;
+        set_mode_active(ActiveMode::KeyboardEnhancement(self.flags), enhanced);
+        Ok(())
+    }
+    /// Gets if the keyboard enhancement flags are pushed
+    #[must_use]
+    pub fn get(&self) -> bool {
+        self.enabled
+    }
+    /// Gets the keyboard enhancement flags this pushes
+    #[must_use]
+    pub fn flags(&self) -> KeyboardEnhancementFlags {
+        self.flags
+    }
+}
+
+impl Drop for KeyboardEnhancementHandler {
+    fn drop(&mut self) {
+        let _ = self.disable();
+    }
+}
+
+/// A clipboard that can be set with `set_clipboard`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ClipboardSelection {
+    /// The regular clipboard used by copy and paste
+    Clipboard,
+    /// The primary selection used by middle click paste (X11 and Wayland)
+    Primary,
+}
+
+impl ClipboardSelection {
+    /// Gets the OSC 52 parameter for the selection
+    const fn parameter(self) -> char {
+        match self {
+            Self::Clipboard => 'c',
+            Self::Primary => 'p',
+        }
+    }
+}
+
+/// What the terminal supports, as reported by the terminal itself
+///
+/// Get it with `os::probe_capabilities`,
+/// anything the terminal didn't reply about is left unsupported
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Capabilities {
+    /// The terminal's name from `TERMINAL_VERSION_QUERY`, e.g. `XTerm` or `kitty`
+    pub name: Option<String>,
+    /// The terminal's version from `TERMINAL_VERSION_QUERY`
+    pub version: Option<String>,
+    /// The features listed in the primary device attributes
+    pub device_attributes: Vec<u16>,
+    /// The terminal type from the secondary device attributes
+    pub terminal_type: Option<u16>,
+    /// The firmware version from the secondary device attributes
+    ///
+    /// Some terminals that don't support `TERMINAL_VERSION_QUERY` put their version here
+    pub firmware_version: Option<u16>,
+    /// If sixel graphics are supported
+    pub sixel: bool,
+    /// If `SYNCHRONIZED_UPDATE_BEGIN` and `SYNCHRONIZED_UPDATE_END` are supported
+    pub synchronized_output: bool,
+    /// If bracketed paste is supported, see `BracketedPasteHandler`
+    pub bracketed_paste: bool,
+    /// If focus reporting is supported, see `FocusReportingHandler`
+    pub focus_reporting: bool,
+    /// If SGR encoded mouse reports are supported, see `MouseCaptureHandler`
+    pub sgr_mouse: bool,
+    /// If the kitty keyboard protocol is supported, see `KeyboardEnhancementHandler`
+    pub keyboard_enhancement: bool,
+}
+
+impl Capabilities {
+    /// DEC private modes that are queried by `os::probe_capabilities`
+    pub(crate) const PROBED_MODES: [u16; 4] = [2026, 2004, 1004, 1006];
+
+    /// Records what a reply from the terminal says it supports
+    ///
+    /// Returns if the event was a reply
+    pub(crate) fn update(&mut self, event: &Event) -> bool {
+        match event {
+            Event::PrimaryDeviceAttributes(attributes) => {
+                // The first attribute is the conformance level, not a feature
+                self.device_attributes = attributes.iter().skip(1).copied().collect();
+                self.sixel = self.device_attributes.contains(&4);
+            }
+            Event::SecondaryDeviceAttributes(attributes) => {
+                self.terminal_type = attributes.first().copied();
+                self.firmware_version = attributes.get(1).copied();
+            }
+            Event::TerminalVersion(text) => {
+                // Usually `name(version)` or `name version`
+                let (name, version) = match text.split_once('(') {
+                    Some((name, version)) => (name, Some(version.trim_end_matches(')'))),
+                    None => match text.split_once(' ') {
+                        Some((name, version)) => (name, Some(version)),
+                        None => (text.as_str(), None),
+                    },
+                };
+                self.name = Some(name.trim().to_string());
+                self.version = version.map(|version| version.trim().to_string());
+            }
+            Event::ModeReport(mode, setting) => {
+                // A permanently reset mode is recognized but can never be enabled
+                let supported = matches!(
+                    setting,
+                    ModeSetting::Set | ModeSetting::Reset | ModeSetting::PermanentlySet
+                );
+                match mode {
+                    2026 => self.synchronized_output = supported,
+                    2004 => self.bracketed_paste = supported,
+                    1004 => self.focus_reporting = supported,
+                    1006 => self.sgr_mouse = supported,
+                    _ => return false,
+                }
+            }
+            Event::KeyboardEnhancementFlags(_) => self.keyboard_enhancement = true,
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[test]
+fn test_capabilities_update() {
+    let mut capabilities = Capabilities::default();
+    let replies = [
+        Event::TerminalVersion("XTerm(388)".to_string()),
+        Event::SecondaryDeviceAttributes(vec![41, 388, 0]),
+        Event::ModeReport(2026, ModeSetting::Reset),
+        Event::ModeReport(2004, ModeSetting::NotRecognized),
+        Event::ModeReport(1004, ModeSetting::PermanentlyReset),
+        Event::ModeReport(1006, ModeSetting::PermanentlySet),
+        Event::PrimaryDeviceAttributes(vec![64, 1, 2, 4, 6, 22]),
+    ];
+    for reply in &replies {
+        assert!(capabilities.update(reply));
+    }
+    assert!(!capabilities.update(&Event::FocusGained));
+
+    assert_eq!(capabilities.name.as_deref(), Some("XTerm"));
+    assert_eq!(capabilities.version.as_deref(), Some("388"));
+    assert_eq!(capabilities.terminal_type, Some(41));
+    assert_eq!(capabilities.firmware_version, Some(388));
+    assert!(capabilities.sixel);
+    assert!(capabilities.synchronized_output);
+    assert!(!capabilities.bracketed_paste);
+    assert!(!capabilities.focus_reporting);
+    assert!(capabilities.sgr_mouse);
+    assert!(!capabilities.keyboard_enhancement);
+
+    capabilities.update(&Event::TerminalVersion("tmux 3.4".to_string()));
+    assert_eq!(capabilities.name.as_deref(), Some("tmux"));
+    assert_eq!(capabilities.version.as_deref(), Some("3.4"));
+}
+
+#[test]
+fn test_active_modes() {
+    set_mode_active(ActiveMode::AltScreen, true);
+    set_mode_active(ActiveMode::BracketedPaste, true);
+    assert_eq!(
+        disable_active_modes(),
+        format!("{BRACKETED_PASTE_DISABLE}{ALT_SCREEN_EXIT}")
+    );
+    assert_eq!(
+        enable_active_modes(),
+        format!("{ALT_SCREEN_ENTER}{BRACKETED_PASTE_ENABLE}")
+    );
+
+    set_mode_active(ActiveMode::AltScreen, false);
+    assert_eq!(disable_active_modes(), BRACKETED_PASTE_DISABLE);
+
+    let mut restored = String::new();
+    restore_active_modes(|code| restored.push_str(code));
+    assert_eq!(restored, BRACKETED_PASTE_DISABLE);
+    assert!(!is_mode_active(ActiveMode::BracketedPaste));
+}
+
+#[test]
+fn test_color_codes() {
+    assert_eq!(Color::Reset.fg(), COLOR_DEFAULT_FG);
+    assert_eq!(Color::Reset.bg(), COLOR_DEFAULT_BG);
+    assert_eq!(Color::RED.fg(), COLOR_RED_FG);
+    assert_eq!(Color::WHITE.bg(), COLOR_WHITE_BG);
+    assert_eq!(Color::BRIGHT_RED.fg(), "\x1b[91m");
+    assert_eq!(Color::BRIGHT_WHITE.bg(), "\x1b[107m");
+    assert_eq!(Color::Indexed(208).fg(), "\x1b[38;5;208m");
+    assert_eq!(Color::Rgb(1, 2, 3).bg(), rgb_color_code_bg(1, 2, 3));
+    assert_eq!(Color::Reset.underline(), "\x1b[59m");
+    assert_eq!(Color::BRIGHT_BLUE.underline(), "\x1b[58;5;12m");
+    assert_eq!(Color::Rgb(1, 2, 3).underline(), "\x1b[58;2;1;2;3m");
+    assert_eq!(Color::GREEN.to_string(), Color::GREEN.fg());
+}
+
+#[test]
+fn test_color_downsample() {
+    use ColorSupport::{Ansi16, Ansi256, None, TrueColor};
+
+    let orange = Color::Rgb(255, 135, 0);
+    assert_eq!(orange.downsample(TrueColor), orange);
+    assert_eq!(orange.downsample(Ansi256), Color::Indexed(208));
+    assert_eq!(orange.downsample(Ansi16), Color::YELLOW);
+    assert_eq!(orange.downsample(None), Color::Reset);
+
+    assert_eq!(
+        Color::Rgb(128, 128, 128).downsample(Ansi256),
+        Color::Indexed(244)
+    );
+    assert_eq!(Color::Rgb(0, 0, 0).downsample(Ansi256), Color::Indexed(16));
+    assert_eq!(
+        Color::Rgb(250, 10, 10).downsample(Ansi16),
+        Color::BRIGHT_RED
+    );
+
+    assert_eq!(Color::Indexed(9).downsample(Ansi16), Color::BRIGHT_RED);
+    assert_eq!(Color::Indexed(196).downsample(Ansi16), Color::BRIGHT_RED);
+    assert_eq!(Color::Indexed(196).downsample(Ansi256), Color::Indexed(196));
+    assert_eq!(Color::Indexed(232).downsample(Ansi16), Color::BLACK);
+    assert_eq!(Color::BLUE.downsample(Ansi16), Color::BLUE);
+
+    for index in 16..=255 {
+        let (red, green, blue) = indexed_rgb(index);
+        assert_eq!(closest_indexed(red, green, blue), index);
     }
 }
+
+#[test]
+fn test_style_transition() {
+    let plain = Style::new();
+    let bold = plain.attrs(Attributes::BOLD);
+
+    assert_eq!(plain.to_string(), STYLE_RESET);
+    assert_eq!(bold.fg(Color::RED).to_string(), "\x1b[0;1;31m");
+    assert_eq!(bold.transition(&bold), "");
+    assert_eq!(plain.transition(&bold), STYLE_BOLD);
+    assert_eq!(bold.transition(&plain), STYLE_RESET);
+    assert_eq!(
+        bold.transition(&plain.attrs(Attributes::ITALIC)),
+        "\x1b[0;3m"
+    );
+
+    // Removing bold resets dim as well so it is set again
+    let blue = plain.bg(Color::BLUE);
+    let dim = blue.attrs(Attributes::DIM);
+    assert_eq!(blue.attrs(Attributes::BOLD).transition(&blue), "\x1b[22m");
+    assert_eq!(blue.attrs(Attributes::BOLD).transition(&dim), "\x1b[22;2m");
+    assert_eq!(
+        blue.attrs(Attributes::BOLD | Attributes::DIM)
+            .transition(&dim),
+        "\x1b[22;2m"
+    );
+
+    let italic = plain.attrs(Attributes::ITALIC);
+    assert_eq!(italic.transition(&italic.fg(Color::BLUE)), COLOR_BLUE_FG);
+    assert_eq!(italic.fg(Color::BLUE).transition(&italic), COLOR_DEFAULT_FG);
+
+    // Resetting is shorter than undoing each part
+    let busy = Style::new()
+        .fg(Color::Rgb(1, 2, 3))
+        .bg(Color::Indexed(4))
+        .attrs(Attributes::ITALIC | Attributes::UNDERLINE | Attributes::STRIKETHROUGH);
+    assert_eq!(busy.transition(&plain), STYLE_RESET);
+}
@@ -0,0 +1,77 @@
+//! Minimal base64 (RFC 4648) encoding and decoding, used for the clipboard
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes bytes as padded base64
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |acc, (i, &byte)| {
+            acc | u32::from(byte) << (16 - 8 * i)
+        });
+        // 3 bytes become 4 characters, a partial chunk of n bytes needs n + 1 of them
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0b11_1111;
+                encoded.push(char::from(ALPHABET[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes base64, with or without padding
+///
+/// Returns `None` if it isn't valid base64
+pub fn decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    if encoded.len() % 4 == 1 {
+        return None;
+    }
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.chunks(4) {
+        let mut group = 0u32;
+        for (i, &byte) in chunk.iter().enumerate() {
+            group |= decode_char(byte)? << (18 - 6 * i);
+        }
+        // 4 characters become 3 bytes, a partial chunk of n characters holds n - 1 of them
+        decoded.extend_from_slice(&group.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(decoded)
+}
+
+/// Gets the 6 bit value of a base64 character
+fn decode_char(byte: u8) -> Option<u32> {
+    let value = match byte {
+        b'A'..=b'Z' => byte - b'A',
+        b'a'..=b'z' => byte - b'a' + 26,
+        b'0'..=b'9' => byte - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => return None,
+    };
+    Some(u32::from(value))
+}
+
+#[test]
+fn test_base64() {
+    let cases: &[(&[u8], &str)] = &[
+        (b"", ""),
+        (b"f", "Zg=="),
+        (b"fo", "Zm8="),
+        (b"foo", "Zm9v"),
+        (b"foob", "Zm9vYg=="),
+        (b"fooba", "Zm9vYmE="),
+        (b"foobar", "Zm9vYmFy"),
+        (&[0xff, 0xfe, 0x00], "//4A"),
+    ];
+    for &(bytes, encoded) in cases {
+        assert_eq!(encode(bytes), encoded);
+        assert_eq!(decode(encoded).as_deref(), Some(bytes));
+    }
+    assert_eq!(decode("Zm8").as_deref(), Some(&b"fo"[..]));
+    assert_eq!(decode("Zm9vY"), None);
+    assert_eq!(decode("Zm9v!A=="), None);
+}
@@ -0,0 +1,510 @@
+//! Various input functions, structs, etc.
+//!
+//! Very incomplete currently
+
+use crate::ansi::ClipboardSelection;
+
+use std::ops::{BitOr, BitOrAssign};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+mod parser;
+
+pub use crate::os::input::*;
+pub use parser::InputParser;
+
+static ESCAPE_DELAY_MICROS: AtomicU64 = AtomicU64::new(50_000);
+
+/// Sets how long to wait after an Escape byte for the rest of an escape sequence
+///
+/// If nothing arrives within this delay the byte is reported as `KeyCode::Escape`,
+/// otherwise it is parsed as an escape sequence or Alt + key.
+/// Raise it for slow connections (e.g. SSH) where sequences may be split up
+///
+/// Defaults to 50ms
+///
+/// Has no effect on Windows where the Escape key is reported directly
+pub fn set_escape_delay(delay: Duration) {
+    let micros = u64::try_from(delay.as_micros()).unwrap_or(u64::MAX);
+    ESCAPE_DELAY_MICROS.store(micros, Ordering::Relaxed);
+}
+
+/// Gets how long to wait after an Escape byte for the rest of an escape sequence
+///
+/// See `set_escape_delay`
+#[must_use]
+pub fn escape_delay() -> Duration {
+    Duration::from_micros(ESCAPE_DELAY_MICROS.load(Ordering::Relaxed))
+}
+
+static META_8BIT: AtomicBool = AtomicBool::new(false);
+
+/// Sets if input bytes with the 8th bit set are treated as Alt + key
+///
+/// Some terminals (e.g. xterm with `metaSendsEscape` off) send Alt + key this way
+/// instead of prefixing the key with Escape.
+/// This conflicts with UTF-8 input so only enable it when the terminal is known to do this
+///
+/// Defaults to false
+///
+/// Has no effect on Windows
+pub fn set_8bit_meta(enabled: bool) {
+    META_8BIT.store(enabled, Ordering::Relaxed);
+}
+
+/// Gets if input bytes with the 8th bit set are treated as Alt + key
+///
+/// See `set_8bit_meta`
+#[must_use]
+pub fn is_8bit_meta() -> bool {
+    META_8BIT.load(Ordering::Relaxed)
+}
+
+/// Different events that can happen through the terminal
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// An event that happens upon a key being pressed
+    Key(KeyEvent),
+    /// An event that happens upon the mouse being clicked, moved or scrolled
+    ///
+    /// Requires mouse capture to be enabled, see `ansi::MouseCaptureHandler`
+    Mouse(MouseEvent),
+    /// An event that happens upon text being pasted into the terminal
+    ///
+    /// Requires bracketed paste to be enabled, see `ansi::BracketedPasteHandler`
+    ///
+    /// Invalid UTF-8 in the pasted text is replaced with `U+FFFD`
+    Paste(String),
+    /// An event that happens upon the terminal being resized, with the new (width, height)
+    ///
+    /// Only reported on Unix
+    Resize(u16, u16),
+    /// The process was continued after being suspended,
+    /// see `os::suspend` and `os::enable_job_control`
+    ///
+    /// Raw mode and the modes enabled by the handlers in `ansi` are enabled again before it is
+    /// reported, but the screen should be redrawn
+    ///
+    /// Only reported on Unix
+    Resumed,
+    /// The terminal's reply to `ansi::CURSOR_POSITION_QUERY` with the cursor's (column, row)
+    ///
+    /// Origin is 0, 0
+    CursorPosition(u16, u16),
+    /// The terminal's reply to `ansi::KEYBOARD_ENHANCEMENT_QUERY`
+    /// with the currently enabled keyboard enhancement flags
+    KeyboardEnhancementFlags(KeyboardEnhancementFlags),
+    /// The terminal's reply to `ansi::PRIMARY_DEVICE_ATTRIBUTES_QUERY`
+    /// with the terminal's conformance level followed by the features it supports
+    ///
+    /// e.g. 4 means sixel graphics are supported
+    PrimaryDeviceAttributes(Vec<u16>),
+    /// The terminal's reply to `ansi::SECONDARY_DEVICE_ATTRIBUTES_QUERY`
+    /// with the terminal's type, firmware version, and usually a 0 or its ROM cartridge number
+    SecondaryDeviceAttributes(Vec<u16>),
+    /// The terminal's reply to `ansi::TERMINAL_VERSION_QUERY` with its name and version,
+    /// e.g. `XTerm(388)`
+    TerminalVersion(String),
+    /// The terminal's reply to `ansi::FOREGROUND_COLOR_QUERY`
+    /// with the (red, green, blue) of the default foreground color
+    ForegroundColor(u8, u8, u8),
+    /// The terminal's reply to `ansi::BACKGROUND_COLOR_QUERY`
+    /// with the (red, green, blue) of the default background color
+    BackgroundColor(u8, u8, u8),
+    /// The terminal's reply to `ansi::palette_color_query`
+    /// with the (index, red, green, blue) of a color in the 256 color palette
+    PaletteColor(u8, u8, u8, u8),
+    /// The terminal's reply to `ansi::clipboard_query` with the contents of the clipboard
+    ///
+    /// Invalid UTF-8 in the contents is replaced with `U+FFFD`
+    Clipboard(ClipboardSelection, String),
+    /// The terminal's reply to `ansi::mode_query` with the (mode, setting) of a DEC private mode
+    ModeReport(u16, ModeSetting),
+    /// The terminal's reply to `ansi::WINDOW_PIXEL_SIZE_QUERY`
+    /// with the (width, height) of its text area in pixels
+    WindowPixelSize(u16, u16),
+    /// The terminal's reply to `ansi::CELL_PIXEL_SIZE_QUERY`
+    /// with the (width, height) of a character cell in pixels
+    CellPixelSize(u16, u16),
+    /// An event that happens upon focus to the terminal window being gained
+    ///
+    /// Requires focus reporting to be enabled, see `ansi::FocusReportingHandler`
+    FocusGained,
+    /// An event that happens upon focus to the terminal window being lost
+    ///
+    /// Requires focus reporting to be enabled, see `ansi::FocusReportingHandler`
+    FocusLost,
+}
+
+/// An event that happens upon a key being pressed
+///
+/// `==` compares every field, so with `KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS` or
+/// `KeyboardEnhancementFlags::REPORT_ASSOCIATED_TEXT` enabled a key press doesn't equal
+/// `KeyEvent::new` of the same key. Use `matches` to check for a key binding
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyEvent {
+    /// The key that was pressed
+    pub code: KeyCode,
+    /// The modifier keys that were held while the key was pressed
+    pub modifiers: Modifiers,
+    /// If the key was pressed, repeated, or released
+    ///
+    /// Only terminals with `KeyboardEnhancementFlags::REPORT_EVENT_TYPES` enabled
+    /// report anything but `KeyEventKind::Press`
+    pub kind: KeyEventKind,
+    /// The key that would have been pressed with Shift held
+    ///
+    /// Only reported with `KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS` enabled
+    pub shifted_key: Option<char>,
+    /// The key in the same position on a standard US (PC-101) keyboard layout
+    ///
+    /// Only reported with `KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS` enabled
+    pub base_layout_key: Option<char>,
+    /// The text the key press would have inserted
+    ///
+    /// Only reported with `KeyboardEnhancementFlags::REPORT_ASSOCIATED_TEXT` enabled
+    pub text: Option<String>,
+}
+
+impl KeyEvent {
+    /// Creates a new key press event from a key and the modifiers held with it
+    #[must_use]
+    pub const fn new(code: KeyCode, modifiers: Modifiers) -> Self {
+        Self {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            shifted_key: None,
+            base_layout_key: None,
+            text: None,
+        }
+    }
+    /// Gets if this is {code} with exactly {modifiers} held,
+    /// ignoring the kind, alternate keys and text
+    #[must_use]
+    pub fn matches(&self, code: KeyCode, modifiers: Modifiers) -> bool {
+        self.code == code && self.modifiers == modifiers
+    }
+}
+
+/// If a key was pressed, repeated, or released
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum KeyEventKind {
+    /// The key was pressed
+    #[default]
+    Press,
+    /// The key was held down long enough to repeat
+    Repeat,
+    /// The key was released
+    Release,
+}
+
+/// A key on the keyboard
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    /// The Backspace key
+    Backspace,
+    /// The Up arrow key
+    Up,
+    /// The Down arrow key
+    Down,
+    /// The Left arrow key
+    Left,
+    /// The Right arrow key
+    Right,
+    /// The Home key
+    Home,
+    /// The End key
+    End,
+    /// The PageUp key
+    PageUp,
+    /// The PageDown key
+    PageDown,
+    /// The Tab key
+    ///
+    /// Shift + Tab is reported as this with `Modifiers::SHIFT`
+    Tab,
+    /// The delete key
+    Delete,
+    /// The insert key
+    Insert,
+    /// The f1-f35 keys
+    F(u8),
+    /// Any character inputted by the keyboard
+    ///
+    /// Control characters are reported as their letter with `Modifiers::CTRL`.
+    /// Characters typed with Shift are reported as the shifted character without
+    /// `Modifiers::SHIFT` (e.g. Shift + a is `Char('A')`). When the terminal doesn't say what
+    /// that is (other keys than letters with the kitty keyboard protocol but without
+    /// `KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS`) it is the unshifted character with it
+    Char(char),
+    /// The Escape key
+    Escape,
+    /// The Caps Lock key
+    CapsLock,
+    /// The Scroll Lock key
+    ScrollLock,
+    /// The Num Lock key
+    NumLock,
+    /// The Print Screen key
+    PrintScreen,
+    /// The Pause key
+    Pause,
+    /// The Menu key
+    Menu,
+    /// The keypad's Begin key (keypad 5 with Num Lock off)
+    Begin,
+    /// A modifier key by itself
+    ///
+    /// Only reported with `KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES` enabled
+    Modifier(Modifiers),
+    /// A null byte sent to the terminal
+    ///
+    /// Can mean several different things
+    Null,
+}
+
+/// An event that happens upon the mouse being clicked, moved or scrolled
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MouseEvent {
+    /// What the mouse did
+    pub kind: MouseEventKind,
+    /// The button involved in the event
+    ///
+    /// `None` for movement without a held button, scrolling,
+    /// and releases on terminals that don't report which button was released
+    pub button: Option<MouseButton>,
+    /// The column of the mouse
+    ///
+    /// Origin is 0, 0
+    pub column: u16,
+    /// The row of the mouse
+    ///
+    /// Origin is 0, 0
+    pub row: u16,
+    /// The modifier keys that were held during the event
+    pub modifiers: Modifiers,
+}
+
+/// The different things a mouse can do
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MouseEventKind {
+    /// A button was pressed
+    Press,
+    /// A button was released
+    Release,
+    /// The mouse was moved while a button was held
+    Drag,
+    /// The mouse was moved without a button held
+    Moved,
+    /// The scroll wheel was scrolled up
+    ScrollUp,
+    /// The scroll wheel was scrolled down
+    ScrollDown,
+    /// The scroll wheel was scrolled left
+    ScrollLeft,
+    /// The scroll wheel was scrolled right
+    ScrollRight,
+}
+
+/// A button on the mouse
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    /// The left mouse button
+    Left,
+    /// The middle mouse button (usually the scroll wheel)
+    Middle,
+    /// The right mouse button
+    Right,
+    /// The back side button
+    Back,
+    /// The forward side button
+    Forward,
+}
+
+/// A set of modifier keys held during an event
+///
+/// Combine modifiers with `|`, e.g. `Modifiers::CTRL | Modifiers::SHIFT`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    /// No modifiers
+    pub const NONE: Self = Self(0);
+    /// The Shift key
+    pub const SHIFT: Self = Self(1);
+    /// The Alt key (Option on MacOS)
+    pub const ALT: Self = Self(1 << 1);
+    /// The Control key
+    pub const CTRL: Self = Self(1 << 2);
+    /// The Super key (Windows/Command key)
+    pub const SUPER: Self = Self(1 << 3);
+    /// The Hyper key
+    pub const HYPER: Self = Self(1 << 4);
+    /// The Meta key
+    pub const META: Self = Self(1 << 5);
+
+    /// Creates a set of modifiers from its raw bits
+    ///
+    /// The bits are laid out like xterm/kitty modifier parameters (minus one),
+    /// unknown bits are discarded
+    #[must_use]
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits & 0b11_1111)
+    }
+    /// Gets the raw bits of the set
+    #[must_use]
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+    /// Gets the set with every modifier in both sets
+    ///
+    /// Same as `|` but usable in constants
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+    /// Gets if no modifiers are in the set
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    /// Gets if every modifier in `other` is also in the set
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    /// Adds every modifier in `other` to the set
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+    /// Removes every modifier in `other` from the set
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.insert(rhs);
+    }
+}
+
+/// Enhancements to how keys are reported using the kitty keyboard protocol
+///
+/// Combine flags with `|`, e.g.
+/// `KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES | KeyboardEnhancementFlags::REPORT_EVENT_TYPES`
+///
+/// See `ansi::KeyboardEnhancementHandler` for enabling them
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct KeyboardEnhancementFlags(u8);
+
+impl KeyboardEnhancementFlags {
+    /// No enhancements, keys are reported the legacy way
+    pub const NONE: Self = Self(0);
+    /// Reports keys that are ambiguous in the legacy encoding as escape codes
+    ///
+    /// e.g. Escape, Alt + key, Ctrl + I (Tab), and Ctrl + M (Enter)
+    pub const DISAMBIGUATE_ESCAPE_CODES: Self = Self(1);
+    /// Reports key repeats and releases, see `KeyEvent::kind`
+    pub const REPORT_EVENT_TYPES: Self = Self(1 << 1);
+    /// Reports the shifted and base layout keys, see `KeyEvent::shifted_key`
+    pub const REPORT_ALTERNATE_KEYS: Self = Self(1 << 2);
+    /// Reports every key as an escape code, including text and modifier keys
+    pub const REPORT_ALL_KEYS_AS_ESCAPE_CODES: Self = Self(1 << 3);
+    /// Reports the text a key inserts, see `KeyEvent::text`
+    ///
+    /// Requires `REPORT_ALL_KEYS_AS_ESCAPE_CODES`
+    pub const REPORT_ASSOCIATED_TEXT: Self = Self(1 << 4);
+
+    /// Creates a set of flags from its raw bits
+    ///
+    /// Unknown bits are discarded
+    #[must_use]
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits & 0b1_1111)
+    }
+    /// Gets the raw bits of the set
+    #[must_use]
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+    /// Gets if no flags are in the set
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    /// Gets if every flag in `other` is also in the set
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for KeyboardEnhancementFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The setting of a terminal mode as reported in `Event::ModeReport`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ModeSetting {
+    /// The terminal doesn't know the mode
+    NotRecognized,
+    /// The mode is set
+    Set,
+    /// The mode is reset
+    Reset,
+    /// The mode is set and can't be changed
+    PermanentlySet,
+    /// The mode is reset and can't be changed
+    PermanentlyReset,
+}
+
+impl ModeSetting {
+    /// Gets if the terminal knows the mode
+    #[must_use]
+    pub const fn is_recognized(self) -> bool {
+        !matches!(self, Self::NotRecognized)
+    }
+    /// Gets if the mode is set
+    #[must_use]
+    pub const fn is_set(self) -> bool {
+        matches!(self, Self::Set | Self::PermanentlySet)
+    }
+}
+
+impl From<KeyCode> for KeyEvent {
+    fn from(value: KeyCode) -> Self {
+        Self::new(value, Modifiers::NONE)
+    }
+}
+
+impl From<KeyEvent> for Event {
+    fn from(value: KeyEvent) -> Self {
+        Self::Key(value)
+    }
+}
+
+impl From<MouseEvent> for Event {
+    fn from(value: MouseEvent) -> Self {
+        Self::Mouse(value)
+    }
+}
+
+impl From<KeyCode> for Event {
+    fn from(value: KeyCode) -> Self {
+        Self::Key(value.into())
+    }
+}
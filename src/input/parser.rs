@@ -0,0 +1,1544 @@
+//! Platform independent parsing of terminal input
+
+use super::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyboardEnhancementFlags, ModeSetting, Modifiers,
+    MouseButton, MouseEvent, MouseEventKind,
+};
+
+use crate::ansi::ClipboardSelection;
+use crate::base64;
+
+use std::collections::VecDeque;
+use std::io;
+
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+/// Start of the reply to `ansi::TERMINAL_VERSION_QUERY`: `DCS > | text ST`
+const TERMINAL_VERSION_START: &[u8] = b"\x1bP>|";
+/// Start of an OSC reply (e.g. to `ansi::BACKGROUND_COLOR_QUERY`): `OSC <number> ; text ST`
+const OSC_START: &[u8] = b"\x1b]";
+
+/// Parser that turns raw terminal input bytes into `Event`s
+///
+/// It does no IO itself, bytes are fed to it with `feed` as they arrive (in chunks of any size)
+/// and the parsed events are taken out of it by iterating over it.
+/// Incomplete sequences are kept until the rest of them is fed
+///
+/// ```
+/// use neutuino::input::{Event, InputParser, KeyCode, KeyEvent, Modifiers};
+///
+/// let mut parser = InputParser::new();
+/// parser.feed(b"a\x1b[1;");
+/// parser.feed(b"5A");
+/// assert_eq!(parser.next(), Some(KeyCode::Char('a').into()));
+/// assert_eq!(
+///     parser.next(),
+///     Some(KeyEvent::new(KeyCode::Up, Modifiers::CTRL).into())
+/// );
+/// assert_eq!(parser.next(), None);
+/// ```
+#[derive(Debug, Default)]
+pub struct InputParser {
+    buffer: Vec<u8>,
+    events: VecDeque<Event>,
+    /// How far into a bracketed paste has been searched for its end
+    paste_searched: usize,
+    /// How far into a control string (e.g. a clipboard reply) has been searched for its end
+    control_searched: usize,
+    /// How many cursor position reports have been requested but not parsed yet
+    cursor_reports_expected: usize,
+    /// If a query's reply is being waited for, see `set_awaiting_reply`
+    awaiting_reply: bool,
+    options: ParseOptions,
+}
+
+/// Settings that change how the input is parsed
+#[derive(Debug, Default, Copy, Clone)]
+struct ParseOptions {
+    meta_8bit: bool,
+    cursor_report_expected: bool,
+}
+
+impl InputParser {
+    /// Creates a new parser with nothing buffered
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            events: VecDeque::new(),
+            paste_searched: 0,
+            control_searched: 0,
+            cursor_reports_expected: 0,
+            awaiting_reply: false,
+            options: ParseOptions {
+                meta_8bit: false,
+                cursor_report_expected: false,
+            },
+        }
+    }
+    /// Feeds bytes read from the terminal to the parser
+    ///
+    /// Every complete event in the input is parsed, unrecognised sequences are discarded
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+        self.parse(false);
+    }
+    /// Parses buffered bytes as if no more input will follow them
+    ///
+    /// Call this once input has stopped for a while (e.g. the escape delay),
+    /// so that a lone Escape byte is reported as `KeyCode::Escape`.
+    /// A bracketed paste in progress is kept until its end arrives,
+    /// and so is a reply in progress while one is awaited (see `set_awaiting_reply`)
+    pub fn flush(&mut self) {
+        self.parse(true);
+    }
+    /// Gets if there are bytes buffered that aren't a complete event yet
+    #[must_use]
+    pub fn has_pending(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+    /// Sets if input bytes with the 8th bit set are treated as Alt + key
+    ///
+    /// See `input::set_8bit_meta`
+    pub fn set_8bit_meta(&mut self, enabled: bool) {
+        self.options.meta_8bit = enabled;
+    }
+    /// Tells the parser a cursor position report was requested (e.g. with
+    /// `ansi::CURSOR_POSITION_QUERY`)
+    ///
+    /// Cursor position reports look the same as Shift/Ctrl/Alt + F3 on some terminals
+    /// (`CSI 1;2R` can be either), while one is expected they are parsed as
+    /// `Event::CursorPosition` instead of as a key
+    pub fn expect_cursor_position(&mut self) {
+        self.cursor_reports_expected += 1;
+    }
+    /// Takes back an `expect_cursor_position` whose report isn't going to arrive,
+    /// e.g. because waiting for it timed out
+    pub fn cancel_cursor_position_expectation(&mut self) {
+        self.cursor_reports_expected = self.cursor_reports_expected.saturating_sub(1);
+    }
+
+    /// Sets if the reply to a query (e.g. `ansi::clipboard_query`) is being waited for
+    ///
+    /// Replies can arrive split over several reads (e.g. a large clipboard over SSH),
+    /// while one is awaited an incomplete reply is kept across `flush` instead of
+    /// being parsed as Alt + key followed by the rest of it as keys
+    pub fn set_awaiting_reply(&mut self, awaiting: bool) {
+        self.awaiting_reply = awaiting;
+    }
+
+    fn parse(&mut self, complete: bool) {
+        let mut start = 0;
+        while start < self.buffer.len() {
+            let rest = &self.buffer[start..];
+
+            // Pastes can be huge so search for the end without reparsing from the start
+            if rest.starts_with(PASTE_START) {
+                let search_from = self.paste_searched.max(PASTE_START.len());
+                let Some(end) = rest[search_from..]
+                    .windows(PASTE_END.len())
+                    .position(|window| window == PASTE_END)
+                else {
+                    self.paste_searched = rest.len().saturating_sub(PASTE_END.len() - 1);
+                    break;
+                };
+                let end = search_from + end;
+                let text = String::from_utf8_lossy(&rest[PASTE_START.len()..end]).into_owned();
+                self.events.push_back(Event::Paste(text));
+                self.paste_searched = 0;
+                start += end + PASTE_END.len();
+                continue;
+            }
+
+            // Escape pressed right before a paste, which would otherwise be taken as Alt and
+            // swallow the paste start
+            if rest.first() == Some(&b'\x1b') && rest[1..].starts_with(PASTE_START) {
+                self.events.push_back(KeyCode::Escape.into());
+                start += 1;
+                continue;
+            }
+
+            let reply_complete = complete && !self.awaiting_reply;
+            match parse_control_string(rest, reply_complete, &mut self.control_searched) {
+                ControlString::None => {}
+                ControlString::Incomplete => break,
+                ControlString::Complete(event, len) => {
+                    self.events.extend(event);
+                    start += len;
+                    continue;
+                }
+            }
+
+            let mut iter = BufferIter {
+                bytes: rest[1..].iter(),
+                complete,
+            };
+            self.options.cursor_report_expected = self.cursor_reports_expected > 0;
+            let result = try_parse_event(rest[0], &mut iter, self.options);
+            let consumed = rest.len() - iter.bytes.as_slice().len();
+            match result {
+                Ok(event) => {
+                    if let Event::CursorPosition(..) = event {
+                        self.cursor_reports_expected =
+                            self.cursor_reports_expected.saturating_sub(1);
+                    }
+                    self.events.push_back(event);
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                // Unrecognised input is discarded
+                Err(_) => {}
+            }
+            start += consumed;
+        }
+        self.buffer.drain(..start);
+    }
+}
+
+impl Iterator for InputParser {
+    type Item = Event;
+
+    /// Takes the next parsed event, returns `None` until more input is fed
+    fn next(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+}
+
+/// A control string (e.g. DCS or OSC) at the start of the input
+enum ControlString {
+    /// The input doesn't start with a control string
+    None,
+    /// The input starts with part of a control string
+    Incomplete,
+    /// A control string with its event if it was recognised, and how many bytes it took up
+    Complete(Option<Event>, usize),
+}
+
+/// Parses the control strings terminals reply to queries with
+///
+/// These can't be parsed with the other sequences as they can be interrupted by
+/// the end of the input, which isn't known until `complete`.
+/// Replies can arrive over several reads, so `complete` is only set when none is awaited.
+/// They can also be huge (e.g. clipboard contents), so `searched` keeps track of how far
+/// into the string has been searched for its end between calls
+fn parse_control_string(bytes: &[u8], complete: bool, searched: &mut usize) -> ControlString {
+    // Alt + Shift + P is also `ESC P` and Alt + ] is also `ESC ]`,
+    // so wait to see if a reply follows them
+    if !complete && (TERMINAL_VERSION_START.starts_with(bytes) || OSC_START.starts_with(bytes)) {
+        return ControlString::Incomplete;
+    }
+
+    let (body, osc) = if let Some(body) = bytes.strip_prefix(TERMINAL_VERSION_START) {
+        (body, false)
+    } else if let Some(body) = bytes.strip_prefix(OSC_START)
+        && body.first().is_some_and(u8::is_ascii_digit)
+    {
+        (body, true)
+    } else {
+        return ControlString::None;
+    };
+    let search_from = (*searched).min(body.len());
+    let Some((end, terminator_len)) = find_string_terminator(&body[search_from..]) else {
+        // Nothing more is coming, so this is likely Alt + key
+        if complete {
+            *searched = 0;
+            return ControlString::None;
+        }
+        // The last byte could be the start of ST
+        *searched = body.len().saturating_sub(1);
+        return ControlString::Incomplete;
+    };
+    *searched = 0;
+    let end = search_from + end;
+    let len = bytes.len() - body.len() + end + terminator_len;
+    let body = &body[..end];
+
+    let event = if osc {
+        parse_osc_reply(body)
+    } else {
+        Some(Event::TerminalVersion(
+            String::from_utf8_lossy(body).into_owned(),
+        ))
+    };
+    ControlString::Complete(event, len)
+}
+
+/// Decodes the `<number> ; text` of an OSC reply
+fn parse_osc_reply(body: &[u8]) -> Option<Event> {
+    let body = std::str::from_utf8(body).ok()?;
+    let (number, text) = body.split_once(';')?;
+    match number {
+        "10" => {
+            let (red, green, blue) = parse_color_spec(text)?;
+            Some(Event::ForegroundColor(red, green, blue))
+        }
+        "11" => {
+            let (red, green, blue) = parse_color_spec(text)?;
+            Some(Event::BackgroundColor(red, green, blue))
+        }
+        "4" => {
+            let (index, spec) = text.split_once(';')?;
+            let (red, green, blue) = parse_color_spec(spec)?;
+            Some(Event::PaletteColor(index.parse().ok()?, red, green, blue))
+        }
+        "52" => {
+            let (selection, data) = text.split_once(';')?;
+            let selection = match selection {
+                "p" => ClipboardSelection::Primary,
+                _ => ClipboardSelection::Clipboard,
+            };
+            let contents = base64::decode(data)?;
+            Some(Event::Clipboard(
+                selection,
+                String::from_utf8_lossy(&contents).into_owned(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Decodes an X11 color spec: `rgb:R/G/B` with 1-4 hex digits per channel
+///
+/// Some terminals add an alpha channel (`rgba:R/G/B/A`), which is ignored
+fn parse_color_spec(spec: &str) -> Option<(u8, u8, u8)> {
+    let channels = spec
+        .strip_prefix("rgb:")
+        .or_else(|| spec.strip_prefix("rgba:"))?;
+    let mut channels = channels.split('/').map(|channel| {
+        if !(1..=4).contains(&channel.len()) {
+            return None;
+        }
+        let value = u32::from_str_radix(channel, 16).ok()?;
+        // Scale to 8 bits, e.g. `ffff` and `f` are both 255
+        let max = (1 << (4 * channel.len())) - 1;
+        u8::try_from((value * 255 + max / 2) / max).ok()
+    });
+    let red = channels.next()??;
+    let green = channels.next()??;
+    let blue = channels.next()??;
+    Some((red, green, blue))
+}
+
+/// Finds the end of a control string (e.g. DCS), which is ST (`ESC \\`) or BEL
+///
+/// Returns the position of the terminator and its length
+fn find_string_terminator(bytes: &[u8]) -> Option<(usize, usize)> {
+    bytes.iter().enumerate().find_map(|(i, &byte)| match byte {
+        b'\x07' => Some((i, 1)),
+        b'\x1b' if bytes.get(i + 1) == Some(&b'\\') => Some((i, 2)),
+        _ => None,
+    })
+}
+
+/// Iterates over buffered bytes
+///
+/// Once out of bytes it errors with `WouldBlock` if more may still arrive,
+/// otherwise it ends so a lone Escape can be told apart
+struct BufferIter<'a> {
+    bytes: std::slice::Iter<'a, u8>,
+    complete: bool,
+}
+
+impl Iterator for BufferIter<'_> {
+    type Item = io::Result<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.bytes.next() {
+            Some(&byte) => Some(Ok(byte)),
+            None if self.complete => None,
+            None => Some(Err(io::ErrorKind::WouldBlock.into())),
+        }
+    }
+}
+
+fn next_byte<I>(iter: &mut I) -> io::Result<u8>
+where
+    I: Iterator<Item = io::Result<u8>>,
+{
+    iter.next()
+        .unwrap_or_else(|| Err(io::ErrorKind::UnexpectedEof.into()))
+}
+
+fn try_parse_event<I>(item: u8, iter: &mut I, options: ParseOptions) -> io::Result<Event>
+where
+    I: Iterator<Item = io::Result<u8>>,
+{
+    match item {
+        b'\x1b' => try_parse_ansi_sequence(iter, options),
+        b'\n' | b'\r' => Ok(KeyCode::Char('\n').into()),
+        b'\t' => Ok(KeyCode::Tab.into()),
+        b'\x7f' => Ok(KeyCode::Backspace.into()),
+        b'\0' => Ok(KeyCode::Null.into()),
+        c @ b'\x01'..=b'\x1a' => Ok(ctrl_key((c + 96) as char)),
+        c @ b'\x1c'..=b'\x1f' => Ok(ctrl_key((c + 24) as char)),
+        // Some terminals send Alt + key by setting the 8th bit instead of prefixing Escape
+        c @ 0x80.. if options.meta_8bit => Ok(with_alt(try_parse_event(c & 0x7f, iter, options)?)),
+        c => Ok(KeyCode::Char(parse_utf8_char(c, iter)?).into()),
+    }
+}
+
+fn ctrl_key(c: char) -> Event {
+    KeyEvent::new(KeyCode::Char(c), Modifiers::CTRL).into()
+}
+
+fn with_alt(event: Event) -> Event {
+    match event {
+        Event::Key(mut key) => {
+            key.modifiers |= Modifiers::ALT;
+            Event::Key(key)
+        }
+        event => event,
+    }
+}
+
+fn parse_utf8_char<I>(c: u8, iter: &mut I) -> io::Result<char>
+where
+    I: Iterator<Item = io::Result<u8>>,
+{
+    let error = || io::Error::new(io::ErrorKind::InvalidData, "Input char is not valid UTF-8");
+    let mut bytes = vec![c];
+
+    for _ in 1..=4 {
+        if let Ok(string) = std::str::from_utf8(&bytes) {
+            return Ok(string.chars().next().unwrap());
+        }
+        bytes.push(iter.next().ok_or_else(error)??);
+    }
+    Err(error())
+}
+
+fn try_parse_ansi_sequence<I>(iter: &mut I, options: ParseOptions) -> io::Result<Event>
+where
+    I: Iterator<Item = io::Result<u8>>,
+{
+    match iter.next().transpose()? {
+        // Nothing followed within the escape delay
+        None => Ok(KeyCode::Escape.into()),
+        Some(b'O') => try_parse_ss3_sequence(iter),
+        Some(b'[') => try_parse_csi_sequence(iter, options),
+        // Alt + key is sent as Escape followed by the key,
+        // including control characters (Alt + Ctrl + key) and escape sequences
+        Some(c) => Ok(with_alt(try_parse_event(c, iter, options)?)),
+    }
+}
+
+fn try_parse_ss3_sequence<I>(iter: &mut I) -> io::Result<Event>
+where
+    I: Iterator<Item = io::Result<u8>>,
+{
+    let error = || io::Error::other("Could not parse event");
+    let Some(mut byte) = iter.next().transpose()? else {
+        // Nothing followed within the escape delay
+        return Ok(KeyEvent::new(KeyCode::Char('O'), Modifiers::ALT).into());
+    };
+
+    // Some older terminals send modifiers before the letter: `SS3 <mod> <letter>`
+    let mut modifier_param = None;
+    while byte.is_ascii_digit() {
+        let digit = u32::from(byte - b'0');
+        modifier_param = Some(modifier_param.unwrap_or(0) * 10 + digit);
+        byte = next_byte(iter)?;
+    }
+
+    let &(_, code, mut modifiers) = SS3_KEYS
+        .iter()
+        .find(|(key, ..)| *key == byte)
+        .ok_or_else(error)?;
+    if let Some(param) = modifier_param {
+        modifiers |= parse_modifiers(param).ok_or_else(error)?;
+    }
+    Ok(KeyEvent::new(code, modifiers).into())
+}
+
+fn try_parse_csi_sequence<I>(iter: &mut I, options: ParseOptions) -> io::Result<Event>
+where
+    I: Iterator<Item = io::Result<u8>>,
+{
+    let error = || io::Error::other("Could not parse event");
+    let Some(mut byte) = iter.next().transpose()? else {
+        // Nothing followed within the escape delay
+        return Ok(KeyEvent::new(KeyCode::Char('['), Modifiers::ALT).into());
+    };
+
+    // Linux console F1-F5
+    if byte == b'[' {
+        return match next_byte(iter)? {
+            val @ b'A'..=b'E' => Ok(KeyCode::F(1 + val - b'A').into()),
+            _ => Err(error()),
+        };
+    }
+
+    let mut params = Vec::new();
+    while (0x30..=0x3f).contains(&byte) {
+        params.push(byte);
+        byte = next_byte(iter)?;
+    }
+
+    // SGR mouse reporting: `CSI < b;x;y M` on press and `CSI < b;x;y m` on release
+    if let Some(params) = params.strip_prefix(b"<") {
+        return match (parse_csi_params(params).as_deref(), byte) {
+            (Some(&[button, column, row]), b'M' | b'm') => {
+                parse_mouse_event(button, column, row, byte == b'm')
+                    .map(Event::Mouse)
+                    .ok_or_else(error)
+            }
+            _ => Err(error()),
+        };
+    }
+
+    // Kitty keyboard protocol
+    if byte == b'u' {
+        // Reply to a flags query: `CSI ? flags u`
+        if let Some(flags) = params.strip_prefix(b"?") {
+            return match parse_csi_params(flags).as_deref() {
+                Some(&[flags]) => Ok(Event::KeyboardEnhancementFlags(
+                    KeyboardEnhancementFlags::from_bits(u8::try_from(flags).map_err(|_| error())?),
+                )),
+                _ => Err(error()),
+            };
+        }
+        return parse_kitty_key(&params).map(Event::Key).ok_or_else(error);
+    }
+
+    // Cursor position report: `CSI row;column R`, some terminals add a `?` (DECXCPR)
+    if byte == b'R' {
+        let report = params.strip_prefix(b"?").unwrap_or(&params);
+        if let Some(&[row, column]) = parse_csi_params(report).as_deref() {
+            // `CSI 1;<mod>R` is also F3 with modifiers
+            if options.cursor_report_expected || report != params || row != 1 || column < 2 {
+                return Ok(Event::CursorPosition(
+                    column.saturating_sub(1),
+                    row.saturating_sub(1),
+                ));
+            }
+        }
+    }
+
+    // Device attributes: `CSI ? attributes c` (primary) and `CSI > attributes c` (secondary)
+    if byte == b'c' && matches!(params.first(), Some(b'?' | b'>')) {
+        return parse_device_attributes(&params).ok_or_else(error);
+    }
+
+    // DEC private mode report: `CSI ? mode;setting $ y`
+    // rxvt also uses `$` as the final byte for Shift + key, but never with a `?`
+    if byte == b'$'
+        && let Some(report) = params.strip_prefix(b"?")
+    {
+        if next_byte(iter)? != b'y' {
+            return Err(error());
+        }
+        return parse_mode_report(report).ok_or_else(error);
+    }
+
+    // Window operation reports: `CSI 4;height;width t` and `CSI 6;height;width t`
+    if byte == b't' {
+        return parse_window_report(&params).ok_or_else(error);
+    }
+
+    let flat_params = parse_csi_params(&params);
+
+    if byte == b'M' {
+        return match flat_params.as_deref() {
+            // X10/normal mouse reporting: `CSI M Cb Cx Cy` as raw bytes
+            Some([]) => {
+                let button = next_byte(iter)?;
+                let column = next_byte(iter)?;
+                let row = next_byte(iter)?;
+                parse_mouse_event(
+                    x10_mouse_value(button),
+                    x10_mouse_value(column),
+                    x10_mouse_value(row),
+                    false,
+                )
+            }
+            // urxvt mouse reporting: `CSI Cb;Cx;Cy M` where only Cb is offset by 32
+            Some(&[button, column, row]) => {
+                parse_mouse_event(button.saturating_sub(32), column, row, false)
+            }
+            _ => None,
+        }
+        .map(Event::Mouse)
+        .ok_or_else(error);
+    }
+
+    // Focus reporting: `CSI I` on gaining focus and `CSI O` on losing it
+    if params.is_empty() {
+        match byte {
+            b'I' => return Ok(Event::FocusGained),
+            b'O' => return Ok(Event::FocusLost),
+            _ => {}
+        }
+    }
+
+    parse_csi_key(&params, byte)
+        .map(Event::Key)
+        .ok_or_else(error)
+}
+
+/// Decodes the `?attributes` or `>attributes` parameters of a device attributes report
+fn parse_device_attributes(params: &[u8]) -> Option<Event> {
+    if let Some(attributes) = params.strip_prefix(b"?") {
+        return parse_csi_params(attributes).map(Event::PrimaryDeviceAttributes);
+    }
+    parse_csi_params(params.strip_prefix(b">")?).map(Event::SecondaryDeviceAttributes)
+}
+
+/// Decodes the `kind;height;width` parameters of a window operation report:
+/// `CSI 4;height;width t` for the text area and `CSI 6;height;width t` for a cell, both in pixels
+fn parse_window_report(params: &[u8]) -> Option<Event> {
+    match *parse_csi_params(params)?.as_slice() {
+        [4, height, width] => Some(Event::WindowPixelSize(width, height)),
+        [6, height, width] => Some(Event::CellPixelSize(width, height)),
+        _ => None,
+    }
+}
+
+/// Decodes the `mode;setting` parameters of a DEC private mode report
+fn parse_mode_report(params: &[u8]) -> Option<Event> {
+    let &[mode, setting] = parse_csi_params(params)?.as_slice() else {
+        return None;
+    };
+    let setting = match setting {
+        0 => ModeSetting::NotRecognized,
+        1 => ModeSetting::Set,
+        2 => ModeSetting::Reset,
+        3 => ModeSetting::PermanentlySet,
+        4 => ModeSetting::PermanentlyReset,
+        _ => return None,
+    };
+    Some(Event::ModeReport(mode, setting))
+}
+
+/// Decodes the `CSI 1;<mod>X` and `CSI <n>;<mod>~` key sequences
+///
+/// The kitty keyboard protocol adds the event type as a subparameter: `CSI 1;<mod>:<type>X`
+fn parse_csi_key(params: &[u8], byte: u8) -> Option<KeyEvent> {
+    let params = parse_csi_subparams(params)?;
+    let (mut modifiers, kind) = match params.as_slice() {
+        [] | [_] => (Modifiers::NONE, KeyEventKind::Press),
+        [_, modifiers] => parse_modifiers_and_kind(modifiers)?,
+        _ => return None,
+    };
+    let number = params.first().and_then(|param| param.first()).copied();
+
+    let (code, implied_modifiers) = match byte {
+        b'~' | b'$' | b'^' | b'@' => {
+            let number = number?;
+            let (_, code) = TILDE_KEYS.iter().find(|(key, _)| *key == number)?;
+            let (_, implied_modifiers) = TILDE_FINALS.iter().find(|(key, _)| *key == byte)?;
+            (*code, *implied_modifiers)
+        }
+        _ => {
+            let (_, code, implied_modifiers) = CSI_KEYS.iter().find(|(key, ..)| *key == byte)?;
+            (*code, *implied_modifiers)
+        }
+    };
+    modifiers |= implied_modifiers;
+
+    let mut event = KeyEvent::new(code, modifiers);
+    event.kind = kind;
+    Some(event)
+}
+/// Decodes a kitty keyboard protocol key: `CSI keycode:shifted:base;<mod>:<type>;text u`
+///
+/// Everything but the keycode is optional
+fn parse_kitty_key(params: &[u8]) -> Option<KeyEvent> {
+    let params = parse_csi_subparams(params)?;
+    let (keys, rest) = params.split_first()?;
+    let (mut modifiers, kind) = match rest {
+        [] => (Modifiers::NONE, KeyEventKind::Press),
+        [modifiers] | [modifiers, _] => parse_modifiers_and_kind(modifiers)?,
+        _ => return None,
+    };
+    let text = match rest.get(1) {
+        Some(text) => Some(
+            text.iter()
+                .map(|&codepoint| char::from_u32(codepoint))
+                .collect::<Option<String>>()?,
+        ),
+        None => None,
+    };
+
+    let mut code = kitty_key(*keys.first()?)?;
+    // An empty subparameter means the key wasn't reported
+    let alternate_key = |index| {
+        keys.get(index)
+            .filter(|&&codepoint| codepoint != 0)
+            .and_then(|&codepoint| char::from_u32(codepoint))
+    };
+    let shifted_key = alternate_key(1);
+    let base_layout_key = alternate_key(2);
+
+    // Match the legacy encoding, where Shift + a is reported as `A` without Shift.
+    // Without the shifted key only letters are known to be their uppercase
+    if let KeyCode::Char(c) = code
+        && modifiers.contains(Modifiers::SHIFT)
+    {
+        let shifted =
+            shifted_key.or_else(|| c.is_ascii_lowercase().then(|| c.to_ascii_uppercase()));
+        if let Some(shifted) = shifted {
+            code = KeyCode::Char(shifted);
+            modifiers.remove(Modifiers::SHIFT);
+        }
+    }
+
+    Some(KeyEvent {
+        code,
+        modifiers,
+        kind,
+        shifted_key,
+        base_layout_key,
+        text,
+    })
+}
+
+/// Gets the key for a kitty keyboard protocol keycode
+///
+/// Keys without a unicode character use codepoints in the private use area
+fn kitty_key(keycode: u32) -> Option<KeyCode> {
+    let code = match keycode {
+        8 | 127 => KeyCode::Backspace,
+        9 => KeyCode::Tab,
+        // Enter and keypad Enter
+        13 | 57414 => KeyCode::Char('\n'),
+        27 => KeyCode::Escape,
+        57358 => KeyCode::CapsLock,
+        57359 => KeyCode::ScrollLock,
+        57360 => KeyCode::NumLock,
+        57361 => KeyCode::PrintScreen,
+        57362 => KeyCode::Pause,
+        57363 => KeyCode::Menu,
+        57376..=57398 => KeyCode::F(u8::try_from(keycode - 57376 + 13).ok()?),
+        // Keypad
+        57399..=57408 => KeyCode::Char(char::from_digit(keycode - 57399, 10)?),
+        57409 => KeyCode::Char('.'),
+        57410 => KeyCode::Char('/'),
+        57411 => KeyCode::Char('*'),
+        57412 => KeyCode::Char('-'),
+        57413 => KeyCode::Char('+'),
+        57415 => KeyCode::Char('='),
+        57416 => KeyCode::Char(','),
+        57417 => KeyCode::Left,
+        57418 => KeyCode::Right,
+        57419 => KeyCode::Up,
+        57420 => KeyCode::Down,
+        57421 => KeyCode::PageUp,
+        57422 => KeyCode::PageDown,
+        57423 => KeyCode::Home,
+        57424 => KeyCode::End,
+        57425 => KeyCode::Insert,
+        57426 => KeyCode::Delete,
+        57427 => KeyCode::Begin,
+        // Left and right modifier keys
+        57441 | 57447 => KeyCode::Modifier(Modifiers::SHIFT),
+        57442 | 57448 => KeyCode::Modifier(Modifiers::CTRL),
+        57443 | 57449 => KeyCode::Modifier(Modifiers::ALT),
+        57444 | 57450 => KeyCode::Modifier(Modifiers::SUPER),
+        57445 | 57451 => KeyCode::Modifier(Modifiers::HYPER),
+        57446 | 57452 => KeyCode::Modifier(Modifiers::META),
+        // Media keys and anything else in the private use area
+        0xE000..=0xF8FF => return None,
+        _ => KeyCode::Char(char::from_u32(keycode)?),
+    };
+    Some(code)
+}
+
+/// Decodes the button byte and 1-based position shared by the xterm mouse encodings
+fn parse_mouse_event(button: u16, column: u16, row: u16, released: bool) -> Option<MouseEvent> {
+    let mut modifiers = Modifiers::NONE;
+    if button & 0b0000_0100 != 0 {
+        modifiers |= Modifiers::SHIFT;
+    }
+    if button & 0b0000_1000 != 0 {
+        modifiers |= Modifiers::ALT;
+    }
+    if button & 0b0001_0000 != 0 {
+        modifiers |= Modifiers::CTRL;
+    }
+    let motion = button & 0b0010_0000 != 0;
+
+    let (kind, button) = match button & 0b1100_0011 {
+        0 => (None, Some(MouseButton::Left)),
+        1 => (None, Some(MouseButton::Middle)),
+        2 => (None, Some(MouseButton::Right)),
+        // No button, either movement or a release that doesn't say which button
+        3 if motion => (Some(MouseEventKind::Moved), None),
+        3 => (Some(MouseEventKind::Release), None),
+        64 => (Some(MouseEventKind::ScrollUp), None),
+        65 => (Some(MouseEventKind::ScrollDown), None),
+        66 => (Some(MouseEventKind::ScrollLeft), None),
+        67 => (Some(MouseEventKind::ScrollRight), None),
+        128 => (None, Some(MouseButton::Back)),
+        129 => (None, Some(MouseButton::Forward)),
+        _ => return None,
+    };
+    let kind = kind.unwrap_or(if released {
+        MouseEventKind::Release
+    } else if motion {
+        MouseEventKind::Drag
+    } else {
+        MouseEventKind::Press
+    });
+
+    Some(MouseEvent {
+        kind,
+        button,
+        column: column.saturating_sub(1),
+        row: row.saturating_sub(1),
+        modifiers,
+    })
+}
+
+/// Decodes a single byte of an X10 mouse report, which is offset by 32
+///
+/// Positions past column/row 223 don't fit in a byte, depending on the terminal they are either
+/// sent as 0 or wrap around past 255, both of which end up past 223 here instead of
+/// being mistaken for the top left of the screen
+fn x10_mouse_value(byte: u8) -> u16 {
+    u16::from(byte.wrapping_sub(32))
+}
+
+/// Parses the `;` separated numeric parameters of a CSI sequence
+///
+/// Empty parameters are returned as 0
+fn parse_csi_params(bytes: &[u8]) -> Option<Vec<u16>> {
+    if bytes.is_empty() {
+        return Some(Vec::new());
+    }
+    bytes
+        .split(|&b| b == b';')
+        .map(|param| {
+            param.iter().try_fold(0u16, |acc, &b| {
+                if b.is_ascii_digit() {
+                    acc.checked_mul(10)?.checked_add(u16::from(b - b'0'))
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
+
+/// Parses the `;` separated parameters of a CSI sequence along with their `:` separated
+/// subparameters
+///
+/// Empty parameters are returned as 0
+fn parse_csi_subparams(bytes: &[u8]) -> Option<Vec<Vec<u32>>> {
+    if bytes.is_empty() {
+        return Some(Vec::new());
+    }
+    bytes
+        .split(|&b| b == b';')
+        .map(|param| {
+            param
+                .split(|&b| b == b':')
+                .map(|subparam| {
+                    subparam.iter().try_fold(0u32, |acc, &b| {
+                        if b.is_ascii_digit() {
+                            acc.checked_mul(10)?.checked_add(u32::from(b - b'0'))
+                        } else {
+                            None
+                        }
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Converts an xterm style modifier parameter (1 + bitset) into `Modifiers`
+fn parse_modifiers(param: u32) -> Option<Modifiers> {
+    let bits = u8::try_from(param.saturating_sub(1)).ok()?;
+    Some(Modifiers::from_bits(bits))
+}
+
+/// Converts a `<mod>:<type>` parameter into `Modifiers` and `KeyEventKind`
+fn parse_modifiers_and_kind(param: &[u32]) -> Option<(Modifiers, KeyEventKind)> {
+    let (modifiers, kind) = match param {
+        [modifiers] => (*modifiers, 1),
+        [modifiers, kind] => (*modifiers, *kind),
+        _ => return None,
+    };
+    let kind = match kind {
+        0 | 1 => KeyEventKind::Press,
+        2 => KeyEventKind::Repeat,
+        3 => KeyEventKind::Release,
+        _ => return None,
+    };
+    Some((parse_modifiers(modifiers)?, kind))
+}
+
+/// Keys sent as `CSI <n>~` by xterm, VT220, rxvt, the linux console, and screen/tmux
+const TILDE_KEYS: &[(u32, KeyCode)] = &[
+    (1, KeyCode::Home),
+    (2, KeyCode::Insert),
+    (3, KeyCode::Delete),
+    (4, KeyCode::End),
+    (5, KeyCode::PageUp),
+    (6, KeyCode::PageDown),
+    // rxvt
+    (7, KeyCode::Home),
+    (8, KeyCode::End),
+    // rxvt, putty, and the kitty keyboard protocol
+    (11, KeyCode::F(1)),
+    (12, KeyCode::F(2)),
+    (13, KeyCode::F(3)),
+    (14, KeyCode::F(4)),
+    (15, KeyCode::F(5)),
+    (17, KeyCode::F(6)),
+    (18, KeyCode::F(7)),
+    (19, KeyCode::F(8)),
+    (20, KeyCode::F(9)),
+    (21, KeyCode::F(10)),
+    (23, KeyCode::F(11)),
+    (24, KeyCode::F(12)),
+    // VT220
+    (25, KeyCode::F(13)),
+    (26, KeyCode::F(14)),
+    (28, KeyCode::F(15)),
+    (29, KeyCode::F(16)),
+    (31, KeyCode::F(17)),
+    (32, KeyCode::F(18)),
+    (33, KeyCode::F(19)),
+    (34, KeyCode::F(20)),
+];
+
+/// Final bytes for `CSI <n>~` style sequences, rxvt uses different ones to send modifiers
+const TILDE_FINALS: &[(u8, Modifiers)] = &[
+    (b'~', Modifiers::NONE),
+    (b'$', Modifiers::SHIFT),
+    (b'^', Modifiers::CTRL),
+    (b'@', Modifiers::CTRL.union(Modifiers::SHIFT)),
+];
+
+/// Keys sent as `CSI <letter>` along with the modifiers the letter implies
+const CSI_KEYS: &[(u8, KeyCode, Modifiers)] = &[
+    (b'A', KeyCode::Up, Modifiers::NONE),
+    (b'B', KeyCode::Down, Modifiers::NONE),
+    (b'C', KeyCode::Right, Modifiers::NONE),
+    (b'D', KeyCode::Left, Modifiers::NONE),
+    (b'E', KeyCode::Begin, Modifiers::NONE),
+    (b'F', KeyCode::End, Modifiers::NONE),
+    // linux console
+    (b'G', KeyCode::Begin, Modifiers::NONE),
+    (b'H', KeyCode::Home, Modifiers::NONE),
+    (b'P', KeyCode::F(1), Modifiers::NONE),
+    (b'Q', KeyCode::F(2), Modifiers::NONE),
+    (b'R', KeyCode::F(3), Modifiers::NONE),
+    (b'S', KeyCode::F(4), Modifiers::NONE),
+    (b'Z', KeyCode::Tab, Modifiers::SHIFT),
+    // rxvt
+    (b'a', KeyCode::Up, Modifiers::SHIFT),
+    (b'b', KeyCode::Down, Modifiers::SHIFT),
+    (b'c', KeyCode::Right, Modifiers::SHIFT),
+    (b'd', KeyCode::Left, Modifiers::SHIFT),
+];
+
+/// Keys sent as `SS3 <letter>` (`ESC O <letter>`) along with the modifiers the letter implies
+///
+/// Used for F1-F4, the cursor keys in application mode, and the keypad in application mode
+const SS3_KEYS: &[(u8, KeyCode, Modifiers)] = &[
+    (b'A', KeyCode::Up, Modifiers::NONE),
+    (b'B', KeyCode::Down, Modifiers::NONE),
+    (b'C', KeyCode::Right, Modifiers::NONE),
+    (b'D', KeyCode::Left, Modifiers::NONE),
+    (b'E', KeyCode::Begin, Modifiers::NONE),
+    (b'F', KeyCode::End, Modifiers::NONE),
+    (b'H', KeyCode::Home, Modifiers::NONE),
+    (b'P', KeyCode::F(1), Modifiers::NONE),
+    (b'Q', KeyCode::F(2), Modifiers::NONE),
+    (b'R', KeyCode::F(3), Modifiers::NONE),
+    (b'S', KeyCode::F(4), Modifiers::NONE),
+    // rxvt
+    (b'a', KeyCode::Up, Modifiers::CTRL),
+    (b'b', KeyCode::Down, Modifiers::CTRL),
+    (b'c', KeyCode::Right, Modifiers::CTRL),
+    (b'd', KeyCode::Left, Modifiers::CTRL),
+    // Keypad
+    (b'M', KeyCode::Char('\n'), Modifiers::NONE),
+    (b'X', KeyCode::Char('='), Modifiers::NONE),
+    (b'j', KeyCode::Char('*'), Modifiers::NONE),
+    (b'k', KeyCode::Char('+'), Modifiers::NONE),
+    (b'l', KeyCode::Char(','), Modifiers::NONE),
+    (b'm', KeyCode::Char('-'), Modifiers::NONE),
+    (b'n', KeyCode::Char('.'), Modifiers::NONE),
+    (b'o', KeyCode::Char('/'), Modifiers::NONE),
+    (b'p', KeyCode::Char('0'), Modifiers::NONE),
+    (b'q', KeyCode::Char('1'), Modifiers::NONE),
+    (b'r', KeyCode::Char('2'), Modifiers::NONE),
+    (b's', KeyCode::Char('3'), Modifiers::NONE),
+    (b't', KeyCode::Char('4'), Modifiers::NONE),
+    (b'u', KeyCode::Char('5'), Modifiers::NONE),
+    (b'v', KeyCode::Char('6'), Modifiers::NONE),
+    (b'w', KeyCode::Char('7'), Modifiers::NONE),
+    (b'x', KeyCode::Char('8'), Modifiers::NONE),
+    (b'y', KeyCode::Char('9'), Modifiers::NONE),
+];
+
+#[test]
+fn test_parse_utf8() {
+    let string = "abcéŷ¤£€ù%323";
+    let bytes = &mut string.bytes().map(Ok);
+    let chars = string.chars();
+    for c in chars {
+        let b = bytes.next().unwrap().unwrap();
+        let character = parse_utf8_char(b, bytes).unwrap();
+        assert!(c == character);
+    }
+}
+
+#[cfg(test)]
+fn parse_bytes(bytes: &[u8]) -> io::Result<Event> {
+    let mut iter = BufferIter {
+        bytes: bytes.iter(),
+        complete: true,
+    };
+    let first = next_byte(&mut iter)?;
+    try_parse_event(first, &mut iter, ParseOptions::default())
+}
+
+/// Feeds {bytes} to a parser {chunk_size} bytes at a time, then flushes it
+#[cfg(test)]
+fn parse_in_chunks(bytes: &[u8], chunk_size: usize) -> Vec<Event> {
+    let mut parser = InputParser::new();
+    let mut events = Vec::new();
+    for chunk in bytes.chunks(chunk_size) {
+        parser.feed(chunk);
+        events.extend(parser.by_ref());
+    }
+    parser.flush();
+    events.extend(parser);
+    events
+}
+
+#[test]
+fn test_parse_modifiers() {
+    let key = |code, modifiers| Event::Key(KeyEvent::new(code, modifiers));
+
+    assert_eq!(
+        parse_bytes(b"\x1b[A").unwrap(),
+        key(KeyCode::Up, Modifiers::NONE)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[1;5A").unwrap(),
+        key(KeyCode::Up, Modifiers::CTRL)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[15;2~").unwrap(),
+        key(KeyCode::F(5), Modifiers::SHIFT)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[1;6H").unwrap(),
+        key(KeyCode::Home, Modifiers::CTRL | Modifiers::SHIFT)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[3;3~").unwrap(),
+        key(KeyCode::Delete, Modifiers::ALT)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[Z").unwrap(),
+        key(KeyCode::Tab, Modifiers::SHIFT)
+    );
+    assert_eq!(
+        parse_bytes(b"\x01").unwrap(),
+        key(KeyCode::Char('a'), Modifiers::CTRL)
+    );
+}
+
+#[test]
+fn test_parse_sgr_mouse() {
+    let mouse = |kind, button, column, row, modifiers| {
+        Event::Mouse(MouseEvent {
+            kind,
+            button,
+            column,
+            row,
+            modifiers,
+        })
+    };
+    let left = Some(MouseButton::Left);
+
+    assert_eq!(
+        parse_bytes(b"\x1b[<0;10;5M").unwrap(),
+        mouse(MouseEventKind::Press, left, 9, 4, Modifiers::NONE)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[<0;10;5m").unwrap(),
+        mouse(MouseEventKind::Release, left, 9, 4, Modifiers::NONE)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[<32;300;200M").unwrap(),
+        mouse(MouseEventKind::Drag, left, 299, 199, Modifiers::NONE)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[<35;1;1M").unwrap(),
+        mouse(MouseEventKind::Moved, None, 0, 0, Modifiers::NONE)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[<81;2;3M").unwrap(),
+        mouse(MouseEventKind::ScrollDown, None, 1, 2, Modifiers::CTRL)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[<6;1;1M").unwrap(),
+        mouse(
+            MouseEventKind::Press,
+            Some(MouseButton::Right),
+            0,
+            0,
+            Modifiers::SHIFT
+        )
+    );
+    assert!(parse_bytes(b"\x1b[<0;1M").is_err());
+}
+
+#[test]
+fn test_parse_legacy_mouse() {
+    let mouse = |kind, button, column, row| {
+        Event::Mouse(MouseEvent {
+            kind,
+            button,
+            column,
+            row,
+            modifiers: Modifiers::NONE,
+        })
+    };
+    let left = Some(MouseButton::Left);
+
+    assert_eq!(
+        parse_bytes(b"\x1b[M *%").unwrap(),
+        mouse(MouseEventKind::Press, left, 9, 4)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[M#*%").unwrap(),
+        mouse(MouseEventKind::Release, None, 9, 4)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[M \xff\x00").unwrap(),
+        mouse(MouseEventKind::Press, left, 222, 223)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[M \x01!").unwrap(),
+        mouse(MouseEventKind::Press, left, 224, 0)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[32;300;5M").unwrap(),
+        mouse(MouseEventKind::Press, left, 299, 4)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[96;1;1M").unwrap(),
+        mouse(MouseEventKind::ScrollUp, None, 0, 0)
+    );
+}
+
+#[test]
+fn test_parse_bracketed_paste() {
+    let parse = |bytes: &[u8]| {
+        let mut parser = InputParser::new();
+        parser.feed(bytes);
+        parser.next()
+    };
+    assert_eq!(
+        parse(b"\x1b[200~hello\nworld\x1b[A\x1b[201~"),
+        Some(Event::Paste("hello\nworld\x1b[A".to_string()))
+    );
+    assert_eq!(
+        parse(b"\x1b[200~\x1b[201~"),
+        Some(Event::Paste(String::new()))
+    );
+    assert_eq!(
+        parse(b"\x1b[200~a\xffb\x1b[201~"),
+        Some(Event::Paste("a\u{fffd}b".to_string()))
+    );
+    assert_eq!(parse(b"\x1b[200~unterminated"), None);
+
+    // Escape pressed right before pasting
+    let bytes = b"\x1b\x1b[200~abc\x1b[201~";
+    for chunk_size in 1..=bytes.len() {
+        assert_eq!(
+            parse_in_chunks(bytes, chunk_size),
+            [KeyCode::Escape.into(), Event::Paste("abc".to_string())],
+            "chunk size {chunk_size}"
+        );
+    }
+}
+
+#[test]
+fn test_parse_focus() {
+    assert_eq!(parse_bytes(b"\x1b[I").unwrap(), Event::FocusGained);
+    assert_eq!(parse_bytes(b"\x1b[O").unwrap(), Event::FocusLost);
+}
+
+#[test]
+fn test_parse_kitty_keyboard() {
+    let key = |code, modifiers| KeyEvent::new(code, modifiers);
+
+    assert_eq!(
+        parse_bytes(b"\x1b[27u").unwrap(),
+        Event::Key(key(KeyCode::Escape, Modifiers::NONE))
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[105;5u").unwrap(),
+        Event::Key(key(KeyCode::Char('i'), Modifiers::CTRL))
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[13;3u").unwrap(),
+        Event::Key(key(KeyCode::Char('\n'), Modifiers::ALT))
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[57441;2u").unwrap(),
+        Event::Key(key(KeyCode::Modifier(Modifiers::SHIFT), Modifiers::SHIFT))
+    );
+
+    let mut release = key(KeyCode::Up, Modifiers::CTRL);
+    release.kind = KeyEventKind::Release;
+    assert_eq!(parse_bytes(b"\x1b[1;5:3A").unwrap(), Event::Key(release));
+
+    let mut repeat = key(KeyCode::Delete, Modifiers::NONE);
+    repeat.kind = KeyEventKind::Repeat;
+    assert_eq!(parse_bytes(b"\x1b[3;1:2~").unwrap(), Event::Key(repeat));
+
+    let mut shifted = key(KeyCode::Char('A'), Modifiers::NONE);
+    shifted.shifted_key = Some('A');
+    shifted.base_layout_key = Some('a');
+    shifted.text = Some("A".to_string());
+    assert_eq!(
+        parse_bytes(b"\x1b[97:65:97;2;65u").unwrap(),
+        Event::Key(shifted)
+    );
+
+    // Shift + a is the same however it is encoded
+    for bytes in [&b"A"[..], b"\x1b[97;2u", b"\x1b[97:65;2u"] {
+        let Ok(Event::Key(event)) = parse_bytes(bytes) else {
+            panic!("{bytes:?} isn't a key");
+        };
+        assert!(
+            event.matches(KeyCode::Char('A'), Modifiers::NONE),
+            "{bytes:?}"
+        );
+    }
+    assert_eq!(
+        parse_bytes(b"\x1b[97;6u").unwrap(),
+        key(KeyCode::Char('A'), Modifiers::CTRL).into()
+    );
+    // The shifted character of anything but a letter isn't known without the alternate keys
+    assert_eq!(
+        parse_bytes(b"\x1b[49;2u").unwrap(),
+        key(KeyCode::Char('1'), Modifiers::SHIFT).into()
+    );
+
+    let mut cyrillic = key(KeyCode::Char('я'), Modifiers::CTRL);
+    cyrillic.base_layout_key = Some('z');
+    assert_eq!(
+        parse_bytes("\x1b[1103::122;5u".as_bytes()).unwrap(),
+        Event::Key(cyrillic)
+    );
+
+    assert_eq!(
+        parse_bytes(b"\x1b[?5u").unwrap(),
+        Event::KeyboardEnhancementFlags(
+            KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                | KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS
+        )
+    );
+}
+
+#[test]
+fn test_parse_alt() {
+    let alt = |code, modifiers| Event::Key(KeyEvent::new(code, Modifiers::ALT | modifiers));
+
+    assert_eq!(parse_bytes(b"\x1b").unwrap(), KeyCode::Escape.into());
+    assert_eq!(
+        parse_bytes(b"\x1bb").unwrap(),
+        alt(KeyCode::Char('b'), Modifiers::NONE)
+    );
+    assert_eq!(
+        parse_bytes("\x1bé".as_bytes()).unwrap(),
+        alt(KeyCode::Char('é'), Modifiers::NONE)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b\x01").unwrap(),
+        alt(KeyCode::Char('a'), Modifiers::CTRL)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b\x7f").unwrap(),
+        alt(KeyCode::Backspace, Modifiers::NONE)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b\x1b").unwrap(),
+        alt(KeyCode::Escape, Modifiers::NONE)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b\x1b[A").unwrap(),
+        alt(KeyCode::Up, Modifiers::NONE)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[").unwrap(),
+        alt(KeyCode::Char('['), Modifiers::NONE)
+    );
+}
+
+#[test]
+fn test_parse_key_tables() {
+    let key = |code, modifiers| Event::Key(KeyEvent::new(code, modifiers));
+    let none = Modifiers::NONE;
+
+    let cases: &[(&[u8], Event)] = &[
+        (b"\x1b[2~", key(KeyCode::Insert, none)),
+        (b"\x1b[3~", key(KeyCode::Delete, none)),
+        (b"\x1b[5~", key(KeyCode::PageUp, none)),
+        (b"\x1b[6~", key(KeyCode::PageDown, none)),
+        (b"\x1b[1~", key(KeyCode::Home, none)),
+        (b"\x1b[4~", key(KeyCode::End, none)),
+        (b"\x1b[7~", key(KeyCode::Home, none)),
+        (b"\x1b[8~", key(KeyCode::End, none)),
+        (b"\x1b[11~", key(KeyCode::F(1), none)),
+        (b"\x1b[15~", key(KeyCode::F(5), none)),
+        (b"\x1b[24~", key(KeyCode::F(12), none)),
+        (b"\x1b[34~", key(KeyCode::F(20), none)),
+        (b"\x1b[24;2~", key(KeyCode::F(12), Modifiers::SHIFT)),
+        (b"\x1b[3$", key(KeyCode::Delete, Modifiers::SHIFT)),
+        (b"\x1b[5^", key(KeyCode::PageUp, Modifiers::CTRL)),
+        (
+            b"\x1b[2@",
+            key(KeyCode::Insert, Modifiers::CTRL | Modifiers::SHIFT),
+        ),
+        (b"\x1b[[A", key(KeyCode::F(1), none)),
+        (b"\x1b[[E", key(KeyCode::F(5), none)),
+        (b"\x1b[E", key(KeyCode::Begin, none)),
+        (b"\x1b[a", key(KeyCode::Up, Modifiers::SHIFT)),
+        (b"\x1bOA", key(KeyCode::Up, none)),
+        (b"\x1bOH", key(KeyCode::Home, none)),
+        (b"\x1bOF", key(KeyCode::End, none)),
+        (b"\x1bOP", key(KeyCode::F(1), none)),
+        (b"\x1bOS", key(KeyCode::F(4), none)),
+        (b"\x1bO2P", key(KeyCode::F(1), Modifiers::SHIFT)),
+        (b"\x1bOd", key(KeyCode::Left, Modifiers::CTRL)),
+        (b"\x1bOM", key(KeyCode::Char('\n'), none)),
+        (b"\x1bOp", key(KeyCode::Char('0'), none)),
+        (b"\x1bO", key(KeyCode::Char('O'), Modifiers::ALT)),
+    ];
+    for (bytes, event) in cases {
+        assert_eq!(&parse_bytes(bytes).unwrap(), event, "{bytes:?}");
+    }
+    assert!(parse_bytes(b"\x1b[9~").is_err());
+    assert!(parse_bytes(b"\x1bOz").is_err());
+}
+
+#[test]
+fn test_input_parser_chunks() {
+    let bytes = b"ab\x1b[1;5A\x1b[<0;10;5M\x1b[200~pasted \x1b[201~ text\x1b[201~c\x1b";
+    let mut expected = vec![
+        KeyCode::Char('a').into(),
+        KeyCode::Char('b').into(),
+        KeyEvent::new(KeyCode::Up, Modifiers::CTRL).into(),
+        Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Press,
+            button: Some(MouseButton::Left),
+            column: 9,
+            row: 4,
+            modifiers: Modifiers::NONE,
+        }),
+        Event::Paste("pasted ".to_string()),
+        KeyCode::Char(' ').into(),
+        KeyCode::Char('t').into(),
+        KeyCode::Char('e').into(),
+        KeyCode::Char('x').into(),
+        KeyCode::Char('t').into(),
+    ];
+    // The stray paste end is discarded
+    expected.push(KeyCode::Char('c').into());
+
+    // The trailing Escape waits for the flush in case more of a sequence follows
+    expected.push(KeyCode::Escape.into());
+
+    for chunk_size in 1..=bytes.len() {
+        assert_eq!(
+            parse_in_chunks(bytes, chunk_size),
+            expected,
+            "chunk size {chunk_size}"
+        );
+    }
+
+    let mut parser = InputParser::new();
+    parser.feed(bytes);
+    parser.by_ref().for_each(drop);
+    assert!(parser.has_pending());
+    parser.flush();
+    assert!(!parser.has_pending());
+}
+
+#[test]
+fn test_parse_cursor_position() {
+    assert_eq!(
+        parse_bytes(b"\x1b[12;40R").unwrap(),
+        Event::CursorPosition(39, 11)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[?1;2R").unwrap(),
+        Event::CursorPosition(1, 0)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[1;2R").unwrap(),
+        KeyEvent::new(KeyCode::F(3), Modifiers::SHIFT).into()
+    );
+
+    let mut parser = InputParser::new();
+    parser.expect_cursor_position();
+    parser.feed(b"\x1b[1;2R\x1b[1;2R");
+    assert_eq!(parser.next(), Some(Event::CursorPosition(1, 0)));
+    assert_eq!(
+        parser.next(),
+        Some(KeyEvent::new(KeyCode::F(3), Modifiers::SHIFT).into())
+    );
+
+    // A report that never arrives
+    parser.expect_cursor_position();
+    parser.cancel_cursor_position_expectation();
+    parser.feed(b"\x1b[1;2R");
+    assert_eq!(
+        parser.next(),
+        Some(KeyEvent::new(KeyCode::F(3), Modifiers::SHIFT).into())
+    );
+}
+
+#[test]
+fn test_parse_terminal_replies() {
+    assert_eq!(
+        parse_bytes(b"\x1b[?64;1;4;22c").unwrap(),
+        Event::PrimaryDeviceAttributes(vec![64, 1, 4, 22])
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[>41;388;0c").unwrap(),
+        Event::SecondaryDeviceAttributes(vec![41, 388, 0])
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[?2026;2$y").unwrap(),
+        Event::ModeReport(2026, ModeSetting::Reset)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[?1004;0$y").unwrap(),
+        Event::ModeReport(1004, ModeSetting::NotRecognized)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[4;600;800t").unwrap(),
+        Event::WindowPixelSize(800, 600)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[6;20;10t").unwrap(),
+        Event::CellPixelSize(10, 20)
+    );
+    assert_eq!(
+        parse_bytes(b"\x1b[3$").unwrap(),
+        KeyEvent::new(KeyCode::Delete, Modifiers::SHIFT).into()
+    );
+
+    let bytes = b"\x1bP>|XTerm(388)\x1b\\a\x1bP>|kitty(0.31.0)\x07\x1bP";
+    for chunk_size in 1..=bytes.len() {
+        assert_eq!(
+            parse_in_chunks(bytes, chunk_size),
+            [
+                Event::TerminalVersion("XTerm(388)".to_string()),
+                KeyCode::Char('a').into(),
+                Event::TerminalVersion("kitty(0.31.0)".to_string()),
+                KeyEvent::new(KeyCode::Char('P'), Modifiers::ALT).into(),
+            ],
+            "chunk size {chunk_size}"
+        );
+    }
+}
+
+#[test]
+fn test_parse_color_replies() {
+    let bytes = b"\x1b]11;rgb:0000/2b2b/3636\x1b\\\x1b]10;rgb:f/8/0\x07\x1b]4;1;rgba:cdcd/0000/0000/ffff\x1b\\";
+    for chunk_size in 1..=bytes.len() {
+        assert_eq!(
+            parse_in_chunks(bytes, chunk_size),
+            [
+                Event::BackgroundColor(0, 43, 54),
+                Event::ForegroundColor(255, 136, 0),
+                Event::PaletteColor(1, 205, 0, 0),
+            ],
+            "chunk size {chunk_size}"
+        );
+    }
+
+    // Every chunk arriving after the escape delay while the reply is awaited
+    let bytes = b"\x1b]11;rgb:0000/2b2b/3636\x1b\\\x1b]10;rgb:f/8/0\x07";
+    for chunk_size in 1..=bytes.len() {
+        let mut parser = InputParser::new();
+        parser.set_awaiting_reply(true);
+        let mut events = Vec::new();
+        for chunk in bytes.chunks(chunk_size) {
+            parser.feed(chunk);
+            parser.flush();
+            events.extend(parser.by_ref());
+        }
+        assert_eq!(
+            events,
+            [
+                Event::BackgroundColor(0, 43, 54),
+                Event::ForegroundColor(255, 136, 0),
+            ],
+            "chunk size {chunk_size}"
+        );
+    }
+
+    // Once the reply stops being awaited what arrived of it is taken as keys
+    let mut parser = InputParser::new();
+    parser.set_awaiting_reply(true);
+    parser.feed(b"\x1b]11;rgb:0");
+    parser.flush();
+    assert_eq!(parser.next(), None);
+    parser.set_awaiting_reply(false);
+    parser.flush();
+    assert_eq!(
+        parser.collect::<Vec<_>>().first(),
+        Some(&KeyEvent::new(KeyCode::Char(']'), Modifiers::ALT).into())
+    );
+
+    // Alt + ] by itself, and followed by a key
+    let mut parser = InputParser::new();
+    parser.feed(b"\x1b]");
+    assert_eq!(parser.next(), None);
+    parser.flush();
+    assert_eq!(
+        parser.next(),
+        Some(KeyEvent::new(KeyCode::Char(']'), Modifiers::ALT).into())
+    );
+    parser.feed(b"\x1b]1");
+    parser.flush();
+    assert_eq!(
+        parser.collect::<Vec<_>>(),
+        [
+            KeyEvent::new(KeyCode::Char(']'), Modifiers::ALT).into(),
+            KeyCode::Char('1').into()
+        ]
+    );
+}
+
+#[test]
+fn test_parse_clipboard_reply() {
+    let bytes = b"\x1b]52;c;aGVsbG8gd29ybGQ=\x07\x1b]52;p;\x1b\\";
+    for chunk_size in 1..=bytes.len() {
+        assert_eq!(
+            parse_in_chunks(bytes, chunk_size),
+            [
+                Event::Clipboard(ClipboardSelection::Clipboard, "hello world".to_string()),
+                Event::Clipboard(ClipboardSelection::Primary, String::new()),
+            ],
+            "chunk size {chunk_size}"
+        );
+    }
+    // A large clipboard read over several reads with the escape delay passing in between
+    let contents = "quit\n".repeat(1000);
+    let reply = format!("\x1b]52;c;{}\x1b\\", base64::encode(contents.as_bytes()));
+    let mut parser = InputParser::new();
+    parser.set_awaiting_reply(true);
+    for chunk in reply.as_bytes().chunks(1024) {
+        parser.feed(chunk);
+        parser.flush();
+    }
+    assert_eq!(
+        parser.collect::<Vec<_>>(),
+        [Event::Clipboard(ClipboardSelection::Clipboard, contents)]
+    );
+}
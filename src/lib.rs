@@ -11,9 +11,9 @@
 //! - [x] Output (Windows)
 //! - [x] Input (Unix) (Appears to work, more testing needed)
 //! - [ ] Input (Windows) (WIP)
-//! - [ ] Events (Focus reporting, Bracketed-paste) (Unix)
+//! - [x] Events (Focus reporting, Bracketed-paste) (Unix)
 //! - [ ] Events (Focus reporting, Bracketed-paste) (Windows)
-//! - [ ] Mouse input (Unix)
+//! - [x] Mouse input (Unix)
 //! - [ ] Mouse input (Windows)
 //! - [ ] Feature completeness / API cleanup
 
@@ -21,6 +21,8 @@ pub mod ansi;
 pub mod input;
 pub mod os;
 
+mod base64;
+
 pub mod prelude {
     //! Covenience re-export of common members
     pub use crate::ansi::*;
@@ -1,8 +1,13 @@
 //! Collection of functions that help control the terminal
 //!
 //! These are built to work on Windows, Linux, and MacOS
+//!
+//! They use stdin and stdout, on Unix `Tty` does the same for any other terminal
 
-use std::io;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::thread;
+use std::time::Duration;
 
 #[cfg(unix)]
 mod unix;
@@ -16,12 +21,206 @@ pub use unix::*;
 #[cfg(windows)]
 pub use windows::*;
 
+/// Gets if the panic being handled is going to end the process, so the terminal should be
+/// restored, see `install_restore_hook`
+///
+/// Panics abort the process or only unwind the thread they happen in,
+/// which ends the process when it is the main thread
+fn panic_ends_process() -> bool {
+    cfg!(panic = "abort") || thread::current().name() == Some("main")
+}
+
+/// How many colors the terminal can show, see `get_color_support`
+///
+/// Ordered from least to most colors
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ColorSupport {
+    /// Colors shouldn't be used at all, e.g. when output is redirected to a file
+    None,
+    /// The 16 standard colors, `Color::Ansi`
+    Ansi16,
+    /// The 256 color palette, `Color::Indexed`
+    Ansi256,
+    /// 24-bit colors, `Color::Rgb`
+    TrueColor,
+}
+
+/// Gets how many colors stdout supports from the environment
+///
+/// In order of priority:
+/// - `NO_COLOR` set to a non-empty value disables colors
+/// - `CLICOLOR_FORCE` set to anything but `0` enables colors even if stdout isn't a terminal
+/// - Colors are disabled if stdout isn't a terminal, `CLICOLOR` is `0`, or `TERM` is `dumb`
+/// - `COLORTERM` set to `truecolor` or `24bit` and some known `TERM` values enable truecolor
+/// - `TERM` containing `256color` enables the 256 color palette
+///
+/// Windows consoles support truecolor once `enable_ansi` is called
+#[must_use]
+pub fn get_color_support() -> ColorSupport {
+    color_support_from(|name| env::var(name).ok(), io::stdout().is_terminal())
+}
+
+/// Decides the color support from environment variables gotten through {var}
+fn color_support_from(var: impl Fn(&str) -> Option<String>, is_terminal: bool) -> ColorSupport {
+    let set = |name| var(name).is_some_and(|value| !value.is_empty());
+    if set("NO_COLOR") {
+        return ColorSupport::None;
+    }
+    let forced = var("CLICOLOR_FORCE").is_some_and(|value| !value.is_empty() && value != "0");
+    let term = var("TERM").unwrap_or_default();
+    if !forced && (!is_terminal || var("CLICOLOR").as_deref() == Some("0") || term == "dumb") {
+        return ColorSupport::None;
+    }
+
+    let truecolor_terms = [
+        "-direct",
+        "kitty",
+        "alacritty",
+        "wezterm",
+        "ghostty",
+        "foot",
+    ];
+    if matches!(var("COLORTERM").as_deref(), Some("truecolor" | "24bit"))
+        || truecolor_terms.iter().any(|name| term.contains(name))
+    {
+        ColorSupport::TrueColor
+    } else if term.contains("256color") {
+        ColorSupport::Ansi256
+    } else if term.is_empty() && cfg!(windows) {
+        ColorSupport::TrueColor
+    } else if term.is_empty() && !forced {
+        ColorSupport::None
+    } else {
+        ColorSupport::Ansi16
+    }
+}
+
+/// The size of the terminal in cells and pixels, see `get_terminal_size_detailed`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TerminalSize {
+    /// Width in cells
+    pub columns: u16,
+    /// Height in cells
+    pub rows: u16,
+    /// Width of the text area in pixels, 0 if unknown
+    pub width: u16,
+    /// Height of the text area in pixels, 0 if unknown
+    pub height: u16,
+}
+
+impl TerminalSize {
+    /// Gets the width of a cell in pixels, `None` if the size in pixels is unknown
+    #[must_use]
+    pub const fn cell_width(&self) -> Option<u16> {
+        match self.width.checked_div(self.columns) {
+            Some(0) => None,
+            width => width,
+        }
+    }
+    /// Gets the height of a cell in pixels, `None` if the size in pixels is unknown
+    #[must_use]
+    pub const fn cell_height(&self) -> Option<u16> {
+        match self.height.checked_div(self.rows) {
+            Some(0) => None,
+            height => height,
+        }
+    }
+}
+
+/// Settings for raw mode, see `enable_raw_mode_with` and `RawModeHandler::with_options`
+///
+/// ```
+/// use neutuino::os::RawModeOptions;
+///
+/// // Raw mode where Ctrl + C still sends SIGINT and `println!` still works
+/// let options = RawModeOptions::raw()
+///     .keep_signals(true)
+///     .keep_output_processing(true);
+/// ```
+///
+/// On Windows only `keep_signals` has an effect
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RawModeOptions {
+    cbreak: bool,
+    keep_signals: bool,
+    keep_output_processing: bool,
+    min_bytes: u8,
+    /// In tenths of a second
+    read_timeout: u8,
+}
+
+impl RawModeOptions {
+    /// Full raw mode, input is passed through as is and nothing is done to output
+    #[must_use]
+    pub const fn raw() -> Self {
+        Self {
+            cbreak: false,
+            keep_signals: false,
+            keep_output_processing: false,
+            min_bytes: 1,
+            read_timeout: 0,
+        }
+    }
+    /// Cbreak mode, only line buffering and input echoing are disabled
+    ///
+    /// Ctrl + C and Ctrl + Z still send signals, Enter is still read as `\n`,
+    /// and output is still processed
+    #[must_use]
+    pub const fn cbreak() -> Self {
+        Self {
+            cbreak: true,
+            keep_signals: true,
+            keep_output_processing: true,
+            ..Self::raw()
+        }
+    }
+    /// Sets if Ctrl + C, Ctrl + \\, and Ctrl + Z still send SIGINT, SIGQUIT, and SIGTSTP
+    /// instead of being read as input (ISIG)
+    #[must_use]
+    pub const fn keep_signals(mut self, keep: bool) -> Self {
+        self.keep_signals = keep;
+        self
+    }
+    /// Sets if output is still processed (OPOST), e.g. `\n` is still printed as `\r\n`
+    /// so `println!` starts a new line at the left
+    #[must_use]
+    pub const fn keep_output_processing(mut self, keep: bool) -> Self {
+        self.keep_output_processing = keep;
+        self
+    }
+    /// Sets how many bytes a read from stdin waits for (VMIN)
+    ///
+    /// Defaults to 1, `poll_input` waits for input itself so this only affects reading stdin
+    #[must_use]
+    pub const fn min_bytes(mut self, min: u8) -> Self {
+        self.min_bytes = min;
+        self
+    }
+    /// Sets how long a read from stdin waits between bytes, or in total with `min_bytes(0)`
+    /// (VTIME)
+    ///
+    /// Rounded down to tenths of a second up to 25.5 seconds, defaults to 0 (forever)
+    #[must_use]
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = u8::try_from(timeout.as_millis() / 100).unwrap_or(u8::MAX);
+        self
+    }
+}
+
+impl Default for RawModeOptions {
+    fn default() -> Self {
+        Self::raw()
+    }
+}
+
 /// Struct that calls `enable_raw_mode` on construction
 /// and `disable_raw_mode` on destruction
 ///
-/// Prefered over function as it calls `disable_raw_mode` on panic
+/// Prefered over function as it calls `disable_raw_mode` on panic,
+/// see `install_restore_hook` for when it can't be dropped
 pub struct RawModeHandler {
     enabled: bool,
+    options: RawModeOptions,
 }
 
 impl RawModeHandler {
@@ -33,8 +232,21 @@ impl RawModeHandler {
     /// stdin is not a tty,
     /// or it fails to change terminal settings
     pub fn new() -> io::Result<Self> {
-        enable_raw_mode()?;
-        Ok(Self { enabled: true })
+        Self::with_options(RawModeOptions::raw())
+    }
+    /// Creates a new instance and sets the terminal to raw mode with the settings in {options}
+    ///
+    /// # Errors
+    ///
+    /// If there is no stdin,
+    /// stdin is not a tty,
+    /// or it fails to change terminal settings
+    pub fn with_options(options: RawModeOptions) -> io::Result<Self> {
+        enable_raw_mode_with(options)?;
+        Ok(Self {
+            enabled: true,
+            options,
+        })
     }
     /// Enables raw mode
     ///
@@ -74,7 +286,7 @@ impl RawModeHandler {
             return Ok(());
         }
         if raw {
-            enable_raw_mode()?;
+            enable_raw_mode_with(self.options)?;
         } else {
             disable_raw_mode()?;
         }
@@ -86,10 +298,68 @@ impl RawModeHandler {
     pub fn get(&self) -> bool {
         self.enabled
     }
+    /// Gets the settings raw mode is enabled with
+    #[must_use]
+    pub fn options(&self) -> RawModeOptions {
+        self.options
+    }
 }
 
 impl Drop for RawModeHandler {
     fn drop(&mut self) {
-        self.disable().expect("Failed to disable terminal raw mode");
+        // Panicking here would abort if it is dropped while unwinding
+        let _ = self.disable();
     }
 }
+
+#[test]
+fn test_color_support() {
+    let support = |vars: &[(&str, &str)], is_terminal| {
+        color_support_from(
+            |name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| (*value).to_string())
+            },
+            is_terminal,
+        )
+    };
+    let xterm = ("TERM", "xterm-256color");
+
+    assert_eq!(support(&[xterm], true), ColorSupport::Ansi256);
+    assert_eq!(support(&[xterm], false), ColorSupport::None);
+    assert_eq!(support(&[("TERM", "dumb")], true), ColorSupport::None);
+    assert_eq!(support(&[("TERM", "linux")], true), ColorSupport::Ansi16);
+    assert_eq!(
+        support(&[("TERM", "xterm-kitty")], true),
+        ColorSupport::TrueColor
+    );
+    assert_eq!(
+        support(&[xterm, ("COLORTERM", "truecolor")], true),
+        ColorSupport::TrueColor
+    );
+    assert_eq!(
+        support(&[xterm, ("NO_COLOR", "1")], true),
+        ColorSupport::None
+    );
+    assert_eq!(
+        support(&[xterm, ("NO_COLOR", "")], true),
+        ColorSupport::Ansi256
+    );
+    assert_eq!(
+        support(&[xterm, ("CLICOLOR", "0")], true),
+        ColorSupport::None
+    );
+    assert_eq!(
+        support(&[xterm, ("CLICOLOR_FORCE", "1")], false),
+        ColorSupport::Ansi256
+    );
+    assert_eq!(
+        support(&[("CLICOLOR_FORCE", "1")], false),
+        ColorSupport::Ansi16
+    );
+    assert_eq!(
+        support(&[xterm, ("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")], false),
+        ColorSupport::None
+    );
+}
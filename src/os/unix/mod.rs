@@ -1,12 +1,19 @@
-use std::ffi::{c_int, c_short, c_uint, c_ulong, c_ushort};
+use crate::ansi::{CURSOR_SHOW, Capabilities, ClipboardSelection, restore_active_modes};
+
+use super::{RawModeOptions, TerminalSize, panic_ends_process};
+use std::ffi::{c_int, c_short, c_ulong, c_ushort};
 use std::io;
-use std::sync::LazyLock;
+use std::panic;
+use std::sync::Once;
 
 pub mod input;
+mod signal;
+mod tty;
+
+pub use tty::Tty;
 
 unsafe extern "C" {
     fn ioctl(fd: c_int, request: c_ulong, argp: *mut u8) -> c_int;
-    safe fn cfmakeraw(termios: *mut Termios);
     fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
     fn tcsetattr(fd: c_int, optional_actions: c_int, termios: *mut Termios) -> c_int;
 }
@@ -14,17 +21,69 @@ unsafe extern "C" {
 const STDIN_FILENO: c_int = 0;
 const STDOUT_FILENO: c_int = 1;
 const POLLIN: c_short = 1;
-const ICRNL: c_uint = 0x40;
 
 #[cfg(not(target_os = "macos"))]
-const TIOCGWINSZ: c_ulong = 0x5413;
-#[cfg(not(target_os = "macos"))]
-const NCCS: usize = 0x20;
+mod consts {
+    use std::ffi::{c_uint, c_ulong};
+
+    pub type TcFlag = c_uint;
+    pub const TIOCGWINSZ: c_ulong = 0x5413;
+    pub const NCCS: usize = 32;
+    pub const VTIME: usize = 5;
+    pub const VMIN: usize = 6;
+
+    pub const IGNBRK: TcFlag = 0o1;
+    pub const BRKINT: TcFlag = 0o2;
+    pub const PARMRK: TcFlag = 0o10;
+    pub const ISTRIP: TcFlag = 0o40;
+    pub const INLCR: TcFlag = 0o100;
+    pub const IGNCR: TcFlag = 0o200;
+    pub const ICRNL: TcFlag = 0o400;
+    pub const IXON: TcFlag = 0o2000;
+    pub const OPOST: TcFlag = 0o1;
+    pub const CSIZE: TcFlag = 0o60;
+    pub const CS8: TcFlag = 0o60;
+    pub const PARENB: TcFlag = 0o400;
+    pub const ISIG: TcFlag = 0o1;
+    pub const ICANON: TcFlag = 0o2;
+    pub const ECHO: TcFlag = 0o10;
+    pub const ECHONL: TcFlag = 0o100;
+    pub const IEXTEN: TcFlag = 0o100_000;
+}
 
 #[cfg(target_os = "macos")]
-const TIOCGWINSZ: c_ulong = 0x4008_7468;
-#[cfg(target_os = "macos")]
-const NCCS: usize = 0x14;
+mod consts {
+    use std::ffi::c_ulong;
+
+    pub type TcFlag = c_ulong;
+    pub const TIOCGWINSZ: c_ulong = 0x4008_7468;
+    pub const NCCS: usize = 20;
+    pub const VMIN: usize = 16;
+    pub const VTIME: usize = 17;
+
+    pub const IGNBRK: TcFlag = 0x1;
+    pub const BRKINT: TcFlag = 0x2;
+    pub const PARMRK: TcFlag = 0x8;
+    pub const ISTRIP: TcFlag = 0x20;
+    pub const INLCR: TcFlag = 0x40;
+    pub const IGNCR: TcFlag = 0x80;
+    pub const ICRNL: TcFlag = 0x100;
+    pub const IXON: TcFlag = 0x200;
+    pub const OPOST: TcFlag = 0x1;
+    pub const CSIZE: TcFlag = 0x300;
+    pub const CS8: TcFlag = 0x300;
+    pub const PARENB: TcFlag = 0x1000;
+    pub const ISIG: TcFlag = 0x80;
+    pub const ICANON: TcFlag = 0x100;
+    pub const ECHO: TcFlag = 0x8;
+    pub const ECHONL: TcFlag = 0x10;
+    pub const IEXTEN: TcFlag = 0x400;
+}
+
+use consts::{
+    BRKINT, CS8, CSIZE, ECHO, ECHONL, ICANON, ICRNL, IEXTEN, IGNBRK, IGNCR, INLCR, ISIG, ISTRIP,
+    IXON, NCCS, OPOST, PARENB, PARMRK, TIOCGWINSZ, TcFlag, VMIN, VTIME,
+};
 
 #[repr(C)]
 #[derive(Default, Debug, Clone, Copy)]
@@ -38,11 +97,24 @@ struct Winsize {
 #[repr(C)]
 #[derive(Default, Debug, Clone, Copy)]
 struct Termios {
-    iflag: c_uint,
-    oflag: c_uint,
-    cflag: c_uint,
-    lflag: c_uint,
+    iflag: TcFlag,
+    oflag: TcFlag,
+    cflag: TcFlag,
+    lflag: TcFlag,
+    #[cfg(not(target_os = "macos"))]
+    line: u8,
     cc: [u8; NCCS],
+    ispeed: TcFlag,
+    ospeed: TcFlag,
+}
+
+/// Gets the size of the terminal {fd} refers to
+fn get_window_size(fd: c_int) -> io::Result<Winsize> {
+    let mut winsize = Winsize::default();
+    if unsafe { ioctl(fd, TIOCGWINSZ, (&raw mut winsize).cast::<u8>()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(winsize)
 }
 
 fn get_attributes(fd: c_int, termios: &mut Termios) -> io::Result<()> {
@@ -59,32 +131,51 @@ fn set_attributes(fd: c_int, termios: &mut Termios) -> io::Result<()> {
     Ok(())
 }
 
-fn make_raw(termios: &mut Termios) {
-    cfmakeraw(termios);
-    termios.iflag |= !(ICRNL);
+fn make_raw(termios: &mut Termios, options: RawModeOptions) {
+    termios.lflag &= !(ECHO | ICANON);
+    if !options.cbreak {
+        termios.iflag &= !(IGNBRK | BRKINT | PARMRK | ISTRIP | INLCR | IGNCR | ICRNL | IXON);
+        termios.lflag &= !(ECHONL | IEXTEN);
+        termios.cflag &= !(CSIZE | PARENB);
+        termios.cflag |= CS8;
+    }
+    if !options.keep_signals {
+        termios.lflag &= !ISIG;
+    }
+    if !options.keep_output_processing {
+        termios.oflag &= !OPOST;
+    }
+    termios.cc[VMIN] = options.min_bytes;
+    termios.cc[VTIME] = options.read_timeout;
 }
 
-static TERMIOS: LazyLock<Option<Termios>> = LazyLock::new(|| {
-    let mut orig_termios = Termios::default();
-    get_attributes(STDIN_FILENO, &mut orig_termios).ok()?;
-    Some(orig_termios)
-});
-
 /// Enables raw mode, which disables line buffering, input echoing, and output canonicalization
 ///
+/// Uses stdin, see `Tty` for using another terminal
+///
 /// # Errors
 ///
 /// If there is no stdin,
 /// stdin is not a tty,
 /// or it fails to change terminal settings
 pub fn enable_raw_mode() -> io::Result<()> {
-    let mut termios = (*TERMIOS).ok_or(io::Error::other("Failed to get terminal properties"))?;
-    make_raw(&mut termios);
-    set_attributes(STDIN_FILENO, &mut termios)?;
-    Ok(())
+    Tty::stdio().enable_raw_mode()
+}
+
+/// Enables raw mode with the settings in {options}, see `RawModeOptions`
+///
+/// If raw mode is already enabled the settings are replaced
+///
+/// # Errors
+///
+/// If there is no stdin,
+/// stdin is not a tty,
+/// or it fails to change terminal settings
+pub fn enable_raw_mode_with(options: RawModeOptions) -> io::Result<()> {
+    Tty::stdio().enable_raw_mode_with(options)
 }
 
-/// Disables raw mode, which enables line buffering, input echoing, and output canonicalization
+/// Disables raw mode, restoring the terminal settings from before it was enabled
 ///
 /// # Errors
 ///
@@ -92,9 +183,88 @@ pub fn enable_raw_mode() -> io::Result<()> {
 /// stdin is not a tty,
 /// or it fails to change terminal settings
 pub fn disable_raw_mode() -> io::Result<()> {
-    let mut termios = (*TERMIOS).ok_or(io::Error::other("Failed to get terminal properties"))?;
-    set_attributes(STDIN_FILENO, &mut termios)?;
-    Ok(())
+    Tty::stdio().disable_raw_mode()
+}
+
+/// Restores the terminal if the process panics or receives `SIGTERM` or `SIGHUP`
+///
+/// The handlers in `ansi` and `RawModeHandler` only restore the terminal when they are dropped,
+/// which doesn't happen with `panic = "abort"` or when the process is killed. Once this is called
+/// the modes enabled by the handlers are disabled, the cursor is shown, and raw mode is disabled
+/// before the panic message is printed or the process exits. Dropping the handlers afterwards
+/// does nothing
+///
+/// Only panics that end the process restore the terminal: any panic with `panic = "abort"`,
+/// otherwise panics in the main thread. Panics in other threads, which can be caught by
+/// `JoinHandle::join`, leave the terminal alone. Catching a panic in the main thread with
+/// `catch_unwind` still restores the terminal
+///
+/// Signal handlers installed before this are called after the terminal is restored,
+/// and ignored signals stay ignored
+///
+/// Only restores stdin and stdout, calling it again does nothing
+///
+/// # Errors
+///
+/// If it fails to install the signal handlers
+pub fn install_restore_hook() -> io::Result<()> {
+    static PANIC_HOOK: Once = Once::new();
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if panic_ends_process() {
+                restore_terminal();
+            }
+            previous(info);
+        }));
+    });
+    signal::on_exit_signals(restore_terminal)
+}
+
+/// Restores stdin and stdout, see `install_restore_hook`
+///
+/// Writes to stdout directly as it may be locked by the thread that panicked
+fn restore_terminal() {
+    restore_active_modes(|code| signal::write_all_fd(STDOUT_FILENO, code.as_bytes()));
+    signal::write_all_fd(STDOUT_FILENO, CURSOR_SHOW.as_bytes());
+    Tty::stdio().restore_raw_mode();
+}
+
+/// Suspends the process like Ctrl + Z does outside of raw mode, for calling when
+/// Ctrl + Z is read in raw mode
+///
+/// Before stopping, the modes enabled by the handlers in `ansi` are disabled
+/// (e.g. the alternate screen is left) and the original terminal settings are restored.
+/// Once the process is continued (e.g. by `fg`) raw mode and those modes are enabled again
+/// and `Event::Resumed` is queued for `poll_input`, so the screen can be redrawn
+///
+/// When signals are kept (see `RawModeOptions::keep_signals`) Ctrl + Z sends `SIGTSTP`
+/// instead, see `enable_job_control` for handling it the same way
+///
+/// # Errors
+///
+/// If stdout or stdin aren't a TTY,
+/// if it fails to write to stdout, or
+/// if it fails to change terminal settings
+pub fn suspend() -> io::Result<()> {
+    Tty::stdio().suspend()
+}
+
+/// Handles being stopped and continued while raw mode is enabled
+///
+/// Installs `SIGTSTP` and `SIGCONT` handlers, after which `SIGTSTP` (e.g. Ctrl + Z with signals
+/// kept) suspends the process like `suspend` does, and continuing after being stopped by
+/// something else (e.g. `SIGSTOP`) enables raw mode again. Both are then reported by
+/// `poll_input` as `Event::Resumed`
+///
+/// The handlers only wake up `poll_input`, which does the work, so the process isn't stopped
+/// until `poll_input` is called
+///
+/// # Errors
+///
+/// If it fails to install the signal handlers
+pub fn enable_job_control() -> io::Result<()> {
+    signal::handle_job_control()
 }
 
 /// Enables ANSI support on Windows terminals
@@ -118,18 +288,139 @@ pub fn enable_ansi() -> io::Result<()> {
 ///
 /// Returns in (width, height) format
 ///
+/// Uses stdout, see `Tty` for using another terminal
+///
 /// # Errors
 ///
 /// If there is no stdout,
 /// if stdout isn't a TTY, or
 /// if it fails to retrieve the terminal size
 pub fn get_terminal_size() -> io::Result<(u16, u16)> {
-    let mut winsize = Winsize::default();
-    let ioctl_result = unsafe { ioctl(STDOUT_FILENO, TIOCGWINSZ, (&raw mut winsize).cast::<u8>()) };
+    Tty::stdio().get_terminal_size()
+}
 
-    if ioctl_result == 0 {
-        Ok((winsize.col, winsize.row))
-    } else {
-        Err(io::Error::last_os_error())
-    }
+/// Gets the size of the terminal in cells and pixels, e.g. to size images
+///
+/// When the system doesn't know the size in pixels the terminal is asked, so raw mode should be
+/// enabled. Input read while waiting for the replies is kept for `poll_input`.
+/// If the terminal doesn't know either or doesn't reply within a second the size in pixels is 0
+///
+/// Uses stdout, see `Tty` for using another terminal
+///
+/// # Errors
+///
+/// If stdout or stdin aren't a TTY,
+/// if it fails to retrieve the terminal size, or
+/// if it fails to write the queries or read the replies
+pub fn get_terminal_size_detailed() -> io::Result<TerminalSize> {
+    Tty::stdio().get_terminal_size_detailed()
+}
+
+/// Gets the position of the cursor
+///
+/// Returns in (column, row) format, origin is 0, 0
+///
+/// Works by asking the terminal, so raw mode should be enabled
+/// otherwise the reply only arrives after Enter is pressed.
+/// Input read while waiting for the reply is kept for `poll_input`
+///
+/// # Errors
+///
+/// If stdout or stdin aren't a TTY,
+/// if the terminal doesn't reply within a second, or
+/// if it fails to write the query or read the reply
+pub fn get_cursor_position() -> io::Result<(u16, u16)> {
+    Tty::stdio().get_cursor_position()
+}
+
+/// Asks the terminal what it supports
+///
+/// Sends the terminal version, secondary device attributes, DEC private mode, and keyboard
+/// enhancement queries followed by a primary device attributes query. Every terminal replies to
+/// the last one, so once its reply arrives every query the terminal understood has been answered
+///
+/// Raw mode should be enabled, input read while waiting for the replies is kept for `poll_input`
+///
+/// # Errors
+///
+/// If stdout or stdin aren't a TTY,
+/// if the terminal doesn't reply within a second, or
+/// if it fails to write the queries or read the replies
+pub fn probe_capabilities() -> io::Result<Capabilities> {
+    Tty::stdio().probe_capabilities()
+}
+
+/// Gets the terminal's default foreground color
+///
+/// Returns in (red, green, blue) format
+///
+/// Raw mode should be enabled, input read while waiting for the reply is kept for `poll_input`
+///
+/// # Errors
+///
+/// If stdout or stdin aren't a TTY,
+/// if the terminal doesn't support querying colors,
+/// if the terminal doesn't reply within a second, or
+/// if it fails to write the query or read the reply
+pub fn get_foreground_color() -> io::Result<(u8, u8, u8)> {
+    Tty::stdio().get_foreground_color()
+}
+
+/// Gets the terminal's default background color
+///
+/// Returns in (red, green, blue) format
+///
+/// Raw mode should be enabled, input read while waiting for the reply is kept for `poll_input`
+///
+/// # Errors
+///
+/// If stdout or stdin aren't a TTY,
+/// if the terminal doesn't support querying colors,
+/// if the terminal doesn't reply within a second, or
+/// if it fails to write the query or read the reply
+pub fn get_background_color() -> io::Result<(u8, u8, u8)> {
+    Tty::stdio().get_background_color()
+}
+
+/// Gets color {index} of the terminal's 256 color palette
+///
+/// Returns in (red, green, blue) format
+///
+/// Raw mode should be enabled, input read while waiting for the reply is kept for `poll_input`
+///
+/// # Errors
+///
+/// If stdout or stdin aren't a TTY,
+/// if the terminal doesn't support querying colors,
+/// if the terminal doesn't reply within a second, or
+/// if it fails to write the query or read the reply
+pub fn get_palette_color(index: u8) -> io::Result<(u8, u8, u8)> {
+    Tty::stdio().get_palette_color(index)
+}
+
+/// Gets if the terminal's background is dark, e.g. to pick a palette that is readable on it
+///
+/// Raw mode should be enabled, input read while waiting for the reply is kept for `poll_input`
+///
+/// # Errors
+///
+/// Same as `get_background_color`
+pub fn is_dark_background() -> io::Result<bool> {
+    Tty::stdio().is_dark_background()
+}
+
+/// Gets the contents of {selection} through the terminal (OSC 52), which works over SSH
+///
+/// Most terminals don't allow reading the clipboard by default
+///
+/// Raw mode should be enabled, input read while waiting for the reply is kept for `poll_input`
+///
+/// # Errors
+///
+/// If stdout or stdin aren't a TTY,
+/// if the terminal doesn't allow reading the clipboard,
+/// if the terminal doesn't reply within a second, or
+/// if it fails to write the query or read the reply
+pub fn get_clipboard(selection: ClipboardSelection) -> io::Result<String> {
+    Tty::stdio().get_clipboard(selection)
 }
@@ -0,0 +1,342 @@
+//! Signal handling through a self-pipe
+//!
+//! Signal handlers can't do much safely, so they only set a flag and write a byte to a pipe.
+//! The read end of the pipe is polled alongside the input so signals wake up `poll_input`
+
+use std::ffi::{c_int, c_void};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::{mem, ptr};
+
+unsafe extern "C" {
+    fn pipe(fds: *mut c_int) -> c_int;
+    fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+    fn read(fd: c_int, buf: *mut c_void, count: usize) -> isize;
+    fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+    fn raise(signum: c_int) -> c_int;
+    fn sigaction(signum: c_int, action: *const SigAction, old_action: *mut SigAction) -> c_int;
+}
+
+/// `struct sigaction`, used where the previous handler has to be kept and called
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct SigAction {
+    /// `sa_handler`, or `sa_sigaction` with `SA_SIGINFO`
+    handler: usize,
+    #[cfg(not(target_os = "macos"))]
+    mask: [u64; 16],
+    #[cfg(target_os = "macos")]
+    mask: u32,
+    flags: c_int,
+    #[cfg(not(target_os = "macos"))]
+    restorer: usize,
+}
+
+pub const SIGWINCH: c_int = 28;
+const SIGHUP: c_int = 1;
+const SIGTERM: c_int = 15;
+#[cfg(not(target_os = "macos"))]
+const SIGCONT: c_int = 18;
+#[cfg(not(target_os = "macos"))]
+const SIGTSTP: c_int = 20;
+#[cfg(target_os = "macos")]
+const SIGTSTP: c_int = 18;
+#[cfg(target_os = "macos")]
+const SIGCONT: c_int = 19;
+
+const SIG_DFL: usize = 0;
+const SIG_IGN: usize = 1;
+const F_SETFD: c_int = 2;
+const F_GETFL: c_int = 3;
+const F_SETFL: c_int = 4;
+const FD_CLOEXEC: c_int = 1;
+
+#[cfg(not(target_os = "macos"))]
+const O_NONBLOCK: c_int = 0o4000;
+#[cfg(target_os = "macos")]
+const O_NONBLOCK: c_int = 0x4;
+#[cfg(not(target_os = "macos"))]
+const SA_SIGINFO: c_int = 4;
+#[cfg(target_os = "macos")]
+const SA_SIGINFO: c_int = 0x40;
+#[cfg(not(target_os = "macos"))]
+const SA_RESTART: c_int = 0x1000_0000;
+#[cfg(target_os = "macos")]
+const SA_RESTART: c_int = 0x2;
+
+/// The signals that `on_exit_signals` handles
+const EXIT_SIGNALS: [c_int; 2] = [SIGTERM, SIGHUP];
+
+static WAKE_PIPE: OnceLock<Option<c_int>> = OnceLock::new();
+static WAKE_WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+static RESIZED: AtomicBool = AtomicBool::new(false);
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+static CONTINUED: AtomicBool = AtomicBool::new(false);
+static EXIT_HANDLERS: OnceLock<ExitHandlers> = OnceLock::new();
+static EXIT_HANDLERS_INSTALLED: Mutex<bool> = Mutex::new(false);
+/// The `SIGWINCH` handler from before `on_resize`, which it calls
+static PREVIOUS_RESIZE: OnceLock<SigAction> = OnceLock::new();
+/// The `SIGTSTP` handler from before `on_stop`, which it calls
+static PREVIOUS_STOP: OnceLock<SigAction> = OnceLock::new();
+/// The `SIGCONT` handler from before `on_continue`, which it calls
+static PREVIOUS_CONTINUE: OnceLock<SigAction> = OnceLock::new();
+
+/// What the `EXIT_SIGNALS` handlers do
+#[derive(Debug)]
+struct ExitHandlers {
+    restore: fn(),
+    /// The handlers from before, in the same order as `EXIT_SIGNALS`
+    previous: [SigAction; EXIT_SIGNALS.len()],
+}
+
+/// Gets the read end of the pipe that signals write to, creating it and
+/// installing the `SIGWINCH` handler on first use
+///
+/// Returns `None` if the pipe or handlers couldn't be set up
+pub fn wake_fd() -> Option<c_int> {
+    *WAKE_PIPE.get_or_init(|| {
+        let mut fds = [0; 2];
+        if unsafe { pipe(fds.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        for fd in fds {
+            unsafe {
+                fcntl(fd, F_SETFD, FD_CLOEXEC);
+                fcntl(fd, F_SETFL, fcntl(fd, F_GETFL) | O_NONBLOCK);
+            }
+        }
+        WAKE_WRITE_FD.store(fds[1], Ordering::Relaxed);
+
+        install_chained(SIGWINCH, on_resize, &PREVIOUS_RESIZE).ok()?;
+        Some(fds[0])
+    })
+}
+
+/// Installs `SIGTSTP` and `SIGCONT` handlers that wake up `poll_input` to suspend and resume
+///
+/// Handlers from before are still called, calling it again does nothing
+///
+/// # Errors
+///
+/// If it fails to set up the wake pipe or install the handlers
+pub fn handle_job_control() -> io::Result<()> {
+    if wake_fd().is_none() {
+        return Err(io::Error::other("Failed to set up signal handling"));
+    }
+    install_chained(SIGTSTP, on_stop, &PREVIOUS_STOP)?;
+    install_chained(SIGCONT, on_continue, &PREVIOUS_CONTINUE)
+}
+
+/// Installs `SIGTERM` and `SIGHUP` handlers that call {restore} before the process exits
+///
+/// The handlers from before are called afterwards, so an app's own handlers keep working.
+/// Ignored signals stay ignored, calling it again does nothing
+///
+/// # Errors
+///
+/// If it fails to install the handlers
+pub fn on_exit_signals(restore: fn()) -> io::Result<()> {
+    let mut installed = EXIT_HANDLERS_INSTALLED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if *installed {
+        return Ok(());
+    }
+    let mut previous = [SigAction::default(); EXIT_SIGNALS.len()];
+    for (&signum, previous) in EXIT_SIGNALS.iter().zip(&mut previous) {
+        if unsafe { sigaction(signum, ptr::null(), previous) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    // Set before installing so the handlers can't run without it
+    let handlers = EXIT_HANDLERS.get_or_init(|| ExitHandlers { restore, previous });
+
+    let handler = on_exit_signal as extern "C" fn(c_int, *mut c_void, *mut c_void);
+    let action = SigAction {
+        handler: handler as usize,
+        flags: SA_SIGINFO | SA_RESTART,
+        ..SigAction::default()
+    };
+    for (&signum, previous) in EXIT_SIGNALS.iter().zip(&handlers.previous) {
+        if previous.handler == SIG_IGN {
+            continue;
+        }
+        if unsafe { sigaction(signum, &raw const action, ptr::null_mut()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    *installed = true;
+    Ok(())
+}
+
+/// Installs {handler} for {signum}, keeping the handler from before in {previous}
+/// for `call_previous`
+///
+/// Does nothing if {previous} is already set, so it isn't replaced with {handler} itself
+///
+/// # Errors
+///
+/// If it fails to get the handler from before or install {handler}
+fn install_chained(
+    signum: c_int,
+    handler: extern "C" fn(c_int, *mut c_void, *mut c_void),
+    previous: &OnceLock<SigAction>,
+) -> io::Result<()> {
+    if previous.get().is_some() {
+        return Ok(());
+    }
+    let mut old_action = SigAction::default();
+    if unsafe { sigaction(signum, ptr::null(), &raw mut old_action) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if previous.set(old_action).is_err() {
+        return Ok(());
+    }
+    let action = SigAction {
+        handler: handler as usize,
+        flags: SA_SIGINFO | SA_RESTART,
+        ..SigAction::default()
+    };
+    if unsafe { sigaction(signum, &raw const action, ptr::null_mut()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Calls the handler kept by `install_chained` if it is a function,
+/// the default action is left out as the handler replaces it
+fn call_previous(
+    previous: &OnceLock<SigAction>,
+    signum: c_int,
+    info: *mut c_void,
+    context: *mut c_void,
+) {
+    if let Some(previous) = previous.get()
+        && previous.handler != SIG_DFL
+        && previous.handler != SIG_IGN
+    {
+        unsafe { call_handler(previous, signum, info, context) };
+    }
+}
+
+/// Calls the function set as the handler in {action}
+///
+/// # Safety
+///
+/// {action} has to hold a handler function, not `SIG_DFL` or `SIG_IGN`
+unsafe fn call_handler(action: &SigAction, signum: c_int, info: *mut c_void, context: *mut c_void) {
+    unsafe {
+        if action.flags & SA_SIGINFO == 0 {
+            let handler = mem::transmute::<usize, extern "C" fn(c_int)>(action.handler);
+            handler(signum);
+        } else {
+            let handler = mem::transmute::<usize, extern "C" fn(c_int, *mut c_void, *mut c_void)>(
+                action.handler,
+            );
+            handler(signum, info, context);
+        }
+    }
+}
+
+/// Writes {bytes} to {fd} without allocating or locking, so it can be used in signal handlers
+///
+/// Errors are ignored as there is nothing left to do about them
+pub fn write_all_fd(fd: c_int, mut bytes: &[u8]) {
+    while !bytes.is_empty() {
+        let written = unsafe { write(fd, bytes.as_ptr().cast::<c_void>(), bytes.len()) };
+        match usize::try_from(written) {
+            Ok(written) if written > 0 => bytes = &bytes[written..],
+            _ => return,
+        }
+    }
+}
+
+/// Empties the wake pipe so it stops being reported as readable
+pub fn drain_wake_fd(fd: c_int) {
+    let mut buffer = [0u8; 64];
+    while unsafe { read(fd, buffer.as_mut_ptr().cast::<c_void>(), buffer.len()) } > 0 {}
+}
+
+/// Gets if the terminal was resized since the last call
+pub fn take_resized() -> bool {
+    RESIZED.swap(false, Ordering::Relaxed)
+}
+
+/// Gets if the process was asked to stop (e.g. Ctrl + Z with signals kept) since the last call
+pub fn take_stop_requested() -> bool {
+    STOP_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Gets if the process was continued after being stopped by something else since the last call
+pub fn take_continued() -> bool {
+    CONTINUED.swap(false, Ordering::Relaxed)
+}
+
+/// Stops the process until it is continued (e.g. by `fg`)
+pub fn stop() {
+    // The handler only sets a flag, so the default action is needed to actually stop
+    let default = SigAction::default();
+    let mut previous = SigAction::default();
+    unsafe {
+        let replaced = sigaction(SIGTSTP, &raw const default, &raw mut previous) == 0;
+        raise(SIGTSTP);
+        if replaced {
+            sigaction(SIGTSTP, &raw const previous, ptr::null_mut());
+        }
+    }
+    // This continue is already being handled by the caller
+    CONTINUED.store(false, Ordering::Relaxed);
+}
+
+fn wake() {
+    let fd = WAKE_WRITE_FD.load(Ordering::Relaxed);
+    if fd >= 0 {
+        // If the pipe is full it is already going to wake the poll so the result doesn't matter
+        unsafe { write(fd, [1u8].as_ptr().cast::<c_void>(), 1) };
+    }
+}
+
+extern "C" fn on_resize(signum: c_int, info: *mut c_void, context: *mut c_void) {
+    RESIZED.store(true, Ordering::Relaxed);
+    wake();
+    call_previous(&PREVIOUS_RESIZE, signum, info, context);
+}
+
+extern "C" fn on_stop(signum: c_int, info: *mut c_void, context: *mut c_void) {
+    STOP_REQUESTED.store(true, Ordering::Relaxed);
+    wake();
+    call_previous(&PREVIOUS_STOP, signum, info, context);
+}
+
+extern "C" fn on_continue(signum: c_int, info: *mut c_void, context: *mut c_void) {
+    CONTINUED.store(true, Ordering::Relaxed);
+    wake();
+    call_previous(&PREVIOUS_CONTINUE, signum, info, context);
+}
+
+extern "C" fn on_exit_signal(signum: c_int, info: *mut c_void, context: *mut c_void) {
+    let Some(handlers) = EXIT_HANDLERS.get() else {
+        return;
+    };
+    (handlers.restore)();
+
+    let Some(previous) = EXIT_SIGNALS
+        .iter()
+        .position(|&exit_signal| exit_signal == signum)
+        .map(|index| handlers.previous[index])
+    else {
+        return;
+    };
+    unsafe {
+        match previous.handler {
+            // Exit the way the signal would have without the handler
+            SIG_DFL => {
+                sigaction(signum, &raw const previous, ptr::null_mut());
+                raise(signum);
+            }
+            SIG_IGN => {}
+            _ => call_handler(&previous, signum, info, context),
+        }
+    }
+}
@@ -0,0 +1,729 @@
+//! Terminal handles for terminals other than stdin/stdout
+
+use crate::ansi::{
+    BACKGROUND_COLOR_QUERY, CELL_PIXEL_SIZE_QUERY, CURSOR_POSITION_QUERY, Capabilities,
+    ClipboardSelection, FOREGROUND_COLOR_QUERY, KEYBOARD_ENHANCEMENT_QUERY,
+    PRIMARY_DEVICE_ATTRIBUTES_QUERY, SECONDARY_DEVICE_ATTRIBUTES_QUERY, TERMINAL_VERSION_QUERY,
+    WINDOW_PIXEL_SIZE_QUERY, clipboard_query, disable_active_modes, enable_active_modes,
+    mode_query, palette_color_query,
+};
+use crate::input::{Event, InputParser, escape_delay, is_8bit_meta};
+
+use super::{
+    POLLIN, RawModeOptions, STDIN_FILENO, STDOUT_FILENO, TerminalSize, Termios, get_attributes,
+    get_window_size, make_raw, set_attributes, signal,
+};
+use std::collections::VecDeque;
+use std::ffi::{c_int, c_short, c_ulong, c_void};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
+use std::time::{Duration, Instant};
+
+unsafe extern "C" {
+    fn poll(fds: *mut PollFD, nfds: c_ulong, timeout: c_int) -> c_int;
+    fn read(fd: c_int, buf: *mut c_void, count: usize) -> isize;
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct PollFD {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+/// How many bytes are read from the terminal at once
+const READ_BUFFER_SIZE: usize = 4096;
+/// How long to wait for the terminal to reply to a query
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The terminal on stdin and stdout, used by the free functions in `os` and `input`
+static STDIO: Tty = Tty {
+    file: None,
+    input: Mutex::new(InputState {
+        parser: InputParser::new(),
+        deferred: VecDeque::new(),
+    }),
+    raw_mode: Mutex::new(None),
+};
+
+/// A handle to a terminal
+///
+/// The free functions in `os` and `input` use stdin and stdout, which doesn't work when
+/// they are redirected (e.g. `ls | picker | xargs rm`). A `Tty` can use the controlling
+/// terminal (`/dev/tty`) or any other terminal instead
+///
+/// ```no_run
+/// use neutuino::os::Tty;
+/// use std::io::Write;
+///
+/// let mut tty = Tty::open()?;
+/// tty.enable_raw_mode()?;
+/// write!(tty, "Pick one")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// Raw mode is disabled when it is dropped.
+/// Resizes and resumes are reported by whichever handle polls for input first
+///
+/// Only available on Unix
+#[derive(Debug)]
+pub struct Tty {
+    /// `None` for stdin and stdout
+    file: Option<File>,
+    input: Mutex<InputState>,
+    /// The terminal settings from before raw mode was enabled and the settings it was enabled with,
+    /// `None` while it isn't enabled
+    raw_mode: Mutex<Option<(Termios, RawModeOptions)>>,
+}
+
+/// Input state kept between calls
+#[derive(Debug)]
+struct InputState {
+    /// Holds onto partial sequences and events not yet returned
+    parser: InputParser,
+    /// Events read while waiting for a reply from the terminal
+    deferred: VecDeque<Event>,
+}
+
+impl Tty {
+    /// Opens the controlling terminal (`/dev/tty`)
+    ///
+    /// # Errors
+    ///
+    /// If the process has no controlling terminal
+    pub fn open() -> io::Result<Self> {
+        Self::open_path("/dev/tty")
+    }
+    /// Opens the terminal at {path}
+    ///
+    /// # Errors
+    ///
+    /// If it fails to open {path} for reading and writing
+    pub fn open_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Self::from_file(file))
+    }
+    /// Uses an open terminal, which is read from and written to
+    #[must_use]
+    pub fn from_file(file: File) -> Self {
+        Self {
+            file: Some(file),
+            input: Mutex::new(InputState {
+                parser: InputParser::new(),
+                deferred: VecDeque::new(),
+            }),
+            raw_mode: Mutex::new(None),
+        }
+    }
+    /// Gets the terminal on stdin and stdout, which the free functions in `os` and `input` use
+    #[must_use]
+    pub fn stdio() -> &'static Self {
+        &STDIO
+    }
+
+    fn input_fd(&self) -> c_int {
+        self.file.as_ref().map_or(STDIN_FILENO, AsRawFd::as_raw_fd)
+    }
+    fn output_fd(&self) -> c_int {
+        self.file.as_ref().map_or(STDOUT_FILENO, AsRawFd::as_raw_fd)
+    }
+    fn lock_input(&self) -> MutexGuard<'_, InputState> {
+        self.input.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    fn lock_raw_mode(&self) -> MutexGuard<'_, Option<(Termios, RawModeOptions)>> {
+        self.raw_mode.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Enables raw mode, which disables line buffering, input echoing, and output canonicalization
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// or it fails to change terminal settings
+    pub fn enable_raw_mode(&self) -> io::Result<()> {
+        self.enable_raw_mode_with(RawModeOptions::raw())
+    }
+    /// Enables raw mode with the settings in {options}, see `RawModeOptions`
+    ///
+    /// If raw mode is already enabled the settings are replaced
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// or it fails to change terminal settings
+    pub fn enable_raw_mode_with(&self, options: RawModeOptions) -> io::Result<()> {
+        let mut raw_mode = self.lock_raw_mode();
+        // Settings are always based on the original ones so enabling again replaces them
+        let original = if let Some((termios, _)) = *raw_mode {
+            termios
+        } else {
+            let mut termios = Termios::default();
+            get_attributes(self.input_fd(), &mut termios)?;
+            termios
+        };
+        let mut termios = original;
+        make_raw(&mut termios, options);
+        set_attributes(self.input_fd(), &mut termios)?;
+        *raw_mode = Some((original, options));
+        Ok(())
+    }
+    /// Disables raw mode, restoring the terminal settings from before it was enabled
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// or it fails to change terminal settings
+    pub fn disable_raw_mode(&self) -> io::Result<()> {
+        let mut raw_mode = self.lock_raw_mode();
+        if let Some((mut termios, _)) = *raw_mode {
+            set_attributes(self.input_fd(), &mut termios)?;
+            *raw_mode = None;
+        }
+        Ok(())
+    }
+
+    /// Restores the terminal settings from before raw mode was enabled
+    ///
+    /// Doesn't wait for other threads so it can be used in signal handlers,
+    /// does nothing if raw mode is being changed at the same time
+    pub(super) fn restore_raw_mode(&self) {
+        let mut raw_mode = match self.raw_mode.try_lock() {
+            Ok(raw_mode) => raw_mode,
+            Err(TryLockError::Poisoned(error)) => error.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        if let Some((mut termios, _)) = raw_mode.take() {
+            // Nothing can be done about an error here
+            let _ = set_attributes(self.input_fd(), &mut termios);
+        }
+    }
+
+    /// Suspends the process like Ctrl + Z does outside of raw mode, for calling when
+    /// Ctrl + Z is read in raw mode
+    ///
+    /// Before stopping, the modes enabled by the handlers in `ansi` are disabled
+    /// (e.g. the alternate screen is left) and the original terminal settings are restored.
+    /// Once the process is continued (e.g. by `fg`) raw mode and those modes are enabled again
+    /// and `Event::Resumed` is queued for `poll_input`, so the screen can be redrawn
+    ///
+    /// When signals are kept (see `RawModeOptions::keep_signals`) Ctrl + Z sends `SIGTSTP`
+    /// instead, see `os::enable_job_control` for handling it the same way
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// if it fails to write to the terminal, or
+    /// if it fails to change terminal settings
+    pub fn suspend(&self) -> io::Result<()> {
+        self.suspend_and_resume()?;
+        self.lock_input().deferred.push_back(Event::Resumed);
+        Ok(())
+    }
+
+    fn suspend_and_resume(&self) -> io::Result<()> {
+        let mut output = self;
+        output.write_all(disable_active_modes().as_bytes())?;
+        output.flush()?;
+        if let Some((mut termios, _)) = *self.lock_raw_mode() {
+            set_attributes(self.input_fd(), &mut termios)?;
+        }
+        signal::stop();
+        self.resume(true)
+    }
+
+    /// Enables raw mode again with the same settings, and the modes enabled by the handlers
+    /// if they were disabled when suspending
+    fn resume(&self, enable_modes: bool) -> io::Result<()> {
+        if let Some((original, options)) = *self.lock_raw_mode() {
+            let mut termios = original;
+            make_raw(&mut termios, options);
+            set_attributes(self.input_fd(), &mut termios)?;
+        }
+        if enable_modes {
+            let mut output = self;
+            output.write_all(enable_active_modes().as_bytes())?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Gets the size of the terminal
+    ///
+    /// Returns in (width, height) format
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty, or
+    /// if it fails to retrieve the terminal size
+    pub fn get_terminal_size(&self) -> io::Result<(u16, u16)> {
+        let winsize = get_window_size(self.output_fd())?;
+        Ok((winsize.col, winsize.row))
+    }
+
+    /// Gets the size of the terminal in cells and pixels
+    ///
+    /// When the system doesn't know the size in pixels the terminal is asked
+    /// with `WINDOW_PIXEL_SIZE_QUERY` and `CELL_PIXEL_SIZE_QUERY`, so raw mode should be enabled.
+    /// Input read while waiting for the replies is kept for `poll_input`.
+    /// If the terminal doesn't know either or doesn't reply within a second the size in pixels is 0
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// if it fails to retrieve the terminal size, or
+    /// if it fails to write the queries or read the replies
+    pub fn get_terminal_size_detailed(&self) -> io::Result<TerminalSize> {
+        let winsize = get_window_size(self.output_fd())?;
+        let mut size = TerminalSize {
+            columns: winsize.col,
+            rows: winsize.row,
+            width: winsize.xpixel,
+            height: winsize.ypixel,
+        };
+        if size.width != 0 && size.height != 0 {
+            return Ok(size);
+        }
+
+        let (mut window, mut cell) = (None, None);
+        let replies = self.query_terminal(
+            &format!(
+                "{WINDOW_PIXEL_SIZE_QUERY}{CELL_PIXEL_SIZE_QUERY}{PRIMARY_DEVICE_ATTRIBUTES_QUERY}"
+            ),
+            QUERY_TIMEOUT,
+            |_| {},
+            |event| match event {
+                Event::PrimaryDeviceAttributes(_) => ControlFlow::Break(()),
+                Event::WindowPixelSize(width, height) if window.is_none() => {
+                    window = Some((width, height));
+                    ControlFlow::Continue(None)
+                }
+                Event::CellPixelSize(width, height) if cell.is_none() => {
+                    cell = Some((width, height));
+                    ControlFlow::Continue(None)
+                }
+                event => ControlFlow::Continue(Some(event)),
+            },
+        );
+        match replies {
+            // The size in cells is still known, e.g. if raw mode isn't enabled
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => return Ok(size),
+            result => result?,
+        }
+        if let Some((width, height)) = window {
+            (size.width, size.height) = (width, height);
+        } else if let Some((width, height)) = cell {
+            size.width = width.saturating_mul(size.columns);
+            size.height = height.saturating_mul(size.rows);
+        }
+        Ok(size)
+    }
+
+    /// Attempts to fetch input from the terminal
+    ///
+    /// Input is read in chunks, when a chunk holds several events the rest are returned
+    /// by the following calls without reading again
+    ///
+    /// Resizing the terminal is reported as `Event::Resize`, this installs a `SIGWINCH` handler
+    /// the first time it is called (an existing handler is still called).
+    /// Continuing after being stopped is reported as `Event::Resumed`
+    /// once `os::enable_job_control` is called
+    ///
+    /// A lone Escape byte is reported as `KeyCode::Escape` if nothing follows it
+    /// within the escape delay, see `input::set_escape_delay`
+    ///
+    /// # Errors
+    /// If the timeout has expired or
+    /// there was an error getting the data
+    pub fn poll_input(&self, timeout: Duration) -> io::Result<Event> {
+        let mut input = self.lock_input();
+        if let Some(event) = input.deferred.pop_front() {
+            return Ok(event);
+        }
+        self.read_event(&mut input.parser, timeout)
+    }
+
+    /// Gets the position of the cursor
+    ///
+    /// Returns in (column, row) format, origin is 0, 0
+    ///
+    /// Works by asking the terminal, so raw mode should be enabled
+    /// otherwise the reply only arrives after Enter is pressed.
+    /// Input read while waiting for the reply is kept for `poll_input`
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// if the terminal doesn't reply within a second, or
+    /// if it fails to write the query or read the reply
+    pub fn get_cursor_position(&self) -> io::Result<(u16, u16)> {
+        let position = self.query_terminal(
+            CURSOR_POSITION_QUERY,
+            QUERY_TIMEOUT,
+            InputParser::expect_cursor_position,
+            |event| match event {
+                Event::CursorPosition(column, row) => ControlFlow::Break((column, row)),
+                event => ControlFlow::Continue(Some(event)),
+            },
+        );
+        if position.is_err() {
+            // Otherwise Shift/Ctrl + F3 would keep being taken for the missing report
+            self.lock_input()
+                .parser
+                .cancel_cursor_position_expectation();
+        }
+        position
+    }
+
+    /// Asks the terminal what it supports
+    ///
+    /// Sends the terminal version, secondary device attributes, DEC private mode, and keyboard
+    /// enhancement queries followed by a primary device attributes query. Every terminal replies to
+    /// the last one, so once its reply arrives every query the terminal understood has been answered
+    ///
+    /// Raw mode should be enabled, input read while waiting for the replies is kept for `poll_input`
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// if the terminal doesn't reply within a second, or
+    /// if it fails to write the queries or read the replies
+    pub fn probe_capabilities(&self) -> io::Result<Capabilities> {
+        let mut query = String::from(TERMINAL_VERSION_QUERY);
+        query.push_str(SECONDARY_DEVICE_ATTRIBUTES_QUERY);
+        for mode in Capabilities::PROBED_MODES {
+            query.push_str(&mode_query(mode));
+        }
+        query.push_str(KEYBOARD_ENHANCEMENT_QUERY);
+        query.push_str(PRIMARY_DEVICE_ATTRIBUTES_QUERY);
+
+        let mut capabilities = Capabilities::default();
+        self.query_terminal(
+            &query,
+            QUERY_TIMEOUT,
+            |_| {},
+            |event| {
+                let last = matches!(event, Event::PrimaryDeviceAttributes(_));
+                if !capabilities.update(&event) {
+                    ControlFlow::Continue(Some(event))
+                } else if last {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(None)
+                }
+            },
+        )?;
+        Ok(capabilities)
+    }
+
+    /// Gets the terminal's default foreground color
+    ///
+    /// Returns in (red, green, blue) format
+    ///
+    /// Raw mode should be enabled, input read while waiting for the reply is kept for `poll_input`
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// if the terminal doesn't support querying colors,
+    /// if the terminal doesn't reply within a second, or
+    /// if it fails to write the query or read the reply
+    pub fn get_foreground_color(&self) -> io::Result<(u8, u8, u8)> {
+        self.query_with_sentinel(FOREGROUND_COLOR_QUERY, |event| match *event {
+            Event::ForegroundColor(red, green, blue) => Some((red, green, blue)),
+            _ => None,
+        })
+    }
+
+    /// Gets the terminal's default background color
+    ///
+    /// Returns in (red, green, blue) format
+    ///
+    /// Raw mode should be enabled, input read while waiting for the reply is kept for `poll_input`
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// if the terminal doesn't support querying colors,
+    /// if the terminal doesn't reply within a second, or
+    /// if it fails to write the query or read the reply
+    pub fn get_background_color(&self) -> io::Result<(u8, u8, u8)> {
+        self.query_with_sentinel(BACKGROUND_COLOR_QUERY, |event| match *event {
+            Event::BackgroundColor(red, green, blue) => Some((red, green, blue)),
+            _ => None,
+        })
+    }
+
+    /// Gets color {index} of the terminal's 256 color palette
+    ///
+    /// Returns in (red, green, blue) format
+    ///
+    /// Raw mode should be enabled, input read while waiting for the reply is kept for `poll_input`
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// if the terminal doesn't support querying colors,
+    /// if the terminal doesn't reply within a second, or
+    /// if it fails to write the query or read the reply
+    pub fn get_palette_color(&self, index: u8) -> io::Result<(u8, u8, u8)> {
+        self.query_with_sentinel(&palette_color_query(index), |event| match *event {
+            Event::PaletteColor(reply_index, red, green, blue) if reply_index == index => {
+                Some((red, green, blue))
+            }
+            _ => None,
+        })
+    }
+
+    /// Gets if the terminal's background is dark, e.g. to pick a palette that is readable on it
+    ///
+    /// Raw mode should be enabled, input read while waiting for the reply is kept for `poll_input`
+    ///
+    /// # Errors
+    ///
+    /// Same as `get_background_color`
+    pub fn is_dark_background(&self) -> io::Result<bool> {
+        let (red, green, blue) = self.get_background_color()?;
+        // Relative luminance (Rec. 709), scaled by 10000
+        let luminance = 2126 * u32::from(red) + 7152 * u32::from(green) + 722 * u32::from(blue);
+        Ok(luminance < 128 * 10000)
+    }
+
+    /// Gets the contents of {selection} through the terminal (OSC 52), which works over SSH
+    ///
+    /// Most terminals don't allow reading the clipboard by default
+    ///
+    /// Raw mode should be enabled, input read while waiting for the reply is kept for `poll_input`
+    ///
+    /// # Errors
+    ///
+    /// If it isn't a tty,
+    /// if the terminal doesn't allow reading the clipboard,
+    /// if the terminal doesn't reply within a second, or
+    /// if it fails to write the query or read the reply
+    pub fn get_clipboard(&self, selection: ClipboardSelection) -> io::Result<String> {
+        self.query_with_sentinel(&clipboard_query(selection), |event| match event {
+            Event::Clipboard(reply_selection, contents) if *reply_selection == selection => {
+                Some(contents.clone())
+            }
+            _ => None,
+        })
+    }
+
+    /// Writes a query followed by `PRIMARY_DEVICE_ATTRIBUTES_QUERY` and picks the reply out of
+    /// the events read before the device attributes arrive
+    ///
+    /// Every terminal replies to the device attributes query,
+    /// so a terminal that doesn't understand the query can be told apart without waiting
+    fn query_with_sentinel<T>(
+        &self,
+        query: &str,
+        mut reply: impl FnMut(&Event) -> Option<T>,
+    ) -> io::Result<T> {
+        let mut value = None;
+        self.query_terminal(
+            &format!("{query}{PRIMARY_DEVICE_ATTRIBUTES_QUERY}"),
+            QUERY_TIMEOUT,
+            |_| {},
+            |event| {
+                if let Event::PrimaryDeviceAttributes(_) = event {
+                    return ControlFlow::Break(());
+                }
+                match reply(&event) {
+                    Some(reply) if value.is_none() => {
+                        value = Some(reply);
+                        ControlFlow::Continue(None)
+                    }
+                    _ => ControlFlow::Continue(Some(event)),
+                }
+            },
+        )?;
+        value.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "The terminal didn't reply to the query",
+            )
+        })
+    }
+
+    /// Writes a query to the terminal and waits for its reply
+    ///
+    /// `reply` is called with every event that is read until it breaks with the result,
+    /// events it continues with are kept for `poll_input`.
+    /// `before_query` is called on the parser before writing the query
+    fn query_terminal<T>(
+        &self,
+        query: &str,
+        timeout: Duration,
+        before_query: impl FnOnce(&mut InputParser),
+        reply: impl FnMut(Event) -> ControlFlow<T, Option<Event>>,
+    ) -> io::Result<T> {
+        let mut input = self.lock_input();
+        before_query(&mut input.parser);
+        // Keeps a reply that arrives over several reads from being taken as keys
+        input.parser.set_awaiting_reply(true);
+        let result = self.read_replies(&mut input, query, timeout, reply);
+        input.parser.set_awaiting_reply(false);
+        result
+    }
+
+    /// Writes a query and passes the events read to `reply`, see `query_terminal`
+    fn read_replies<T>(
+        &self,
+        input: &mut InputState,
+        query: &str,
+        timeout: Duration,
+        mut reply: impl FnMut(Event) -> ControlFlow<T, Option<Event>>,
+    ) -> io::Result<T> {
+        let mut output = self;
+        output.write_all(query.as_bytes())?;
+        output.flush()?;
+
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            let remaining = deadline.map_or(timeout, |deadline| {
+                deadline.saturating_duration_since(Instant::now())
+            });
+            let event = self.read_event(&mut input.parser, remaining)?;
+            match reply(event) {
+                ControlFlow::Break(value) => return Ok(value),
+                ControlFlow::Continue(Some(event)) => input.deferred.push_back(event),
+                ControlFlow::Continue(None) => {}
+            }
+        }
+    }
+
+    /// Reads the next event from the terminal
+    fn read_event(&self, parser: &mut InputParser, timeout: Duration) -> io::Result<Event> {
+        parser.set_8bit_meta(is_8bit_meta());
+        let wake_fd = signal::wake_fd();
+        let deadline = Instant::now().checked_add(timeout);
+
+        loop {
+            if let Some(event) = parser.next() {
+                return Ok(event);
+            }
+            if signal::take_stop_requested() {
+                self.suspend_and_resume()?;
+                return Ok(Event::Resumed);
+            }
+            if signal::take_continued() {
+                // Stopped by something else (e.g. SIGSTOP), so the shell may have changed the
+                // terminal settings while the modes are still enabled
+                self.resume(false)?;
+                return Ok(Event::Resumed);
+            }
+            if signal::take_resized() {
+                let (width, height) = self.get_terminal_size()?;
+                return Ok(Event::Resize(width, height));
+            }
+
+            let remaining = deadline.map_or(timeout, |deadline| {
+                deadline.saturating_duration_since(Instant::now())
+            });
+            // Give the rest of an escape sequence a moment to arrive before treating it as complete
+            let pending = parser.has_pending();
+            let wait = if pending {
+                remaining.min(escape_delay())
+            } else {
+                remaining
+            };
+
+            let (input_ready, woken) = match poll_fds(self.input_fd(), wake_fd, wait) {
+                Ok(ready) => ready,
+                // Interrupted by a signal, which is handled at the start of the loop
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+            if woken {
+                signal::drain_wake_fd(wake_fd.unwrap_or(-1));
+                continue;
+            }
+            if input_ready {
+                // Everything that was read gets parsed, any extra events are queued for later calls
+                let mut buffer = [0; READ_BUFFER_SIZE];
+                match read_fd(self.input_fd(), &mut buffer) {
+                    Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                    Ok(bytes_read) => parser.feed(&buffer[..bytes_read]),
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                    Err(error) => return Err(error),
+                }
+                continue;
+            }
+            if pending {
+                parser.flush();
+                if let Some(event) = parser.next() {
+                    return Ok(event);
+                }
+            }
+            if wait == remaining {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+        }
+    }
+}
+
+impl Write for &Tty {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.file {
+            Some(ref file) => (&*file).write(buf),
+            // Through `io::stdout` so it stays in order with anything printed
+            None => io::stdout().write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file {
+            Some(ref file) => (&*file).flush(),
+            None => io::stdout().flush(),
+        }
+    }
+}
+
+impl Write for Tty {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+impl Drop for Tty {
+    fn drop(&mut self) {
+        // Nothing can be done about an error here
+        let _ = self.disable_raw_mode();
+    }
+}
+
+/// Reads as many bytes as are available from `fd` (up to the size of the buffer)
+fn read_fd(fd: c_int, buffer: &mut [u8]) -> io::Result<usize> {
+    let bytes_read = unsafe { read(fd, buffer.as_mut_ptr().cast::<c_void>(), buffer.len()) };
+    usize::try_from(bytes_read).map_err(|_| io::Error::last_os_error())
+}
+
+/// Waits until the input or the signal wake pipe have data to read or the timeout expires
+///
+/// Returns if (the input, the wake pipe) have data to read
+fn poll_fds(
+    input_fd: c_int,
+    wake_fd: Option<c_int>,
+    timeout: Duration,
+) -> io::Result<(bool, bool)> {
+    // Negative file descriptors are ignored by poll
+    let mut fds = [input_fd, wake_fd.unwrap_or(-1)].map(|fd| PollFD {
+        fd,
+        events: POLLIN,
+        revents: 0,
+    });
+    let timeout = c_int::try_from(timeout.as_millis()).unwrap_or(c_int::MAX);
+    let result = unsafe { poll(fds.as_mut_ptr(), fds.len() as c_ulong, timeout) };
+
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((fds[0].revents != 0, fds[1].revents != 0))
+}
@@ -1,5 +1,5 @@
 use super::get_stdin_handle;
-use crate::input::{Event, KeyEvent};
+use crate::input::{Event, KeyCode, KeyEvent, Modifiers};
 
 use std::os::windows::raw::HANDLE;
 use std::{io, mem, time::Duration};
@@ -81,13 +81,18 @@ pub fn poll_input(timeout: Duration) -> io::Result<Event> {
     match record.event_type {
         0x10 => {
             // Focus Event
-            Err(io::ErrorKind::InvalidData.into())
+            let focus_event: FocusEventRecord = unsafe { record.event.focus };
+            if focus_event.set_focus == 0 {
+                Ok(Event::FocusLost)
+            } else {
+                Ok(Event::FocusGained)
+            }
         }
         0x1 => {
             // Key Event
             let key_event: KeyEventRecord = unsafe { record.event.key };
             if key_event.key_down == 0 {
-                // return Ok(Event::Key(KeyEvent::Null));
+                // return Ok(KeyCode::Null.into());
                 // I don't quite know why but this seems to happen a lot, until I investigate
                 // more this will have to do
                 return Err(io::ErrorKind::Other.into());
@@ -102,40 +107,62 @@ pub fn poll_input(timeout: Duration) -> io::Result<Event> {
 }
 
 fn parse_key_event(event: &KeyEventRecord) -> KeyEvent {
-    let ctrl = event.control_key_state & (0x0008 | 0x0004) != 0; // LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED
-    let shift = event.control_key_state & 0x0010 != 0; // SHIFT_PRESSED
+    let mut modifiers = Modifiers::NONE;
+    if event.control_key_state & (0x0008 | 0x0004) != 0 {
+        // LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED
+        modifiers |= Modifiers::CTRL;
+    }
+    if event.control_key_state & (0x0002 | 0x0001) != 0 {
+        // LEFT_ALT_PRESSED | RIGHT_ALT_PRESSED
+        modifiers |= Modifiers::ALT;
+    }
+    if event.control_key_state & 0x0010 != 0 {
+        // SHIFT_PRESSED
+        modifiers |= Modifiers::SHIFT;
+    }
 
-    match event.virtual_key_code {
-        0x08 => KeyEvent::Backspace, // VK_BACK
-        0x09 => {
-            if shift {
-                KeyEvent::ShiftTab
+    let unicode_char = unsafe { event.u_char.unicode_char };
+    let code = match event.virtual_key_code {
+        0x08 => KeyCode::Backspace, // VK_BACK
+        0x09 => KeyCode::Tab,
+        0x0D => KeyCode::Char('\n'),
+        0x1B => KeyCode::Escape,
+        0x21 => KeyCode::PageUp,
+        0x22 => KeyCode::PageDown,
+        0x23 => KeyCode::End,
+        0x24 => KeyCode::Home,
+        0x25 => KeyCode::Left,
+        0x26 => KeyCode::Up,
+        0x27 => KeyCode::Right,
+        0x28 => KeyCode::Down,
+        0x2D => KeyCode::Insert,
+        0x2E => KeyCode::Delete,
+        // I don't think anybody is going to try to press F256 clippy
+        #[allow(clippy::cast_possible_truncation)]
+        0x70..=0x87 => KeyCode::F((event.virtual_key_code - 0x6F) as u8), // F1-F24
+        // Ctrl + letter arrives as a control character so use the virtual key code instead
+        #[allow(clippy::cast_possible_truncation)]
+        vk @ 0x41..=0x5A if unicode_char < 0x20 => {
+            // Shifted letters are reported as uppercase without Shift, like the other characters
+            if modifiers.contains(Modifiers::SHIFT) {
+                modifiers.remove(Modifiers::SHIFT);
+                KeyCode::Char(vk as u8 as char)
             } else {
-                KeyEvent::Tab
+                KeyCode::Char((vk as u8).to_ascii_lowercase() as char)
             }
         }
-        0x0D => KeyEvent::Char('\n'),
-        0x1B => KeyEvent::Escape,
-        0x21 => KeyEvent::PageUp,
-        0x22 => KeyEvent::PageDown,
-        0x23 => KeyEvent::End,
-        0x24 => KeyEvent::Home,
-        0x25 => KeyEvent::Left,
-        0x26 => KeyEvent::Up,
-        0x27 => KeyEvent::Right,
-        0x28 => KeyEvent::Down,
-        0x2D => KeyEvent::Insert,
-        0x2E => KeyEvent::Delete,
-        // I don't think anybody is going to try to press F256 clippy
-        #[allow(clippy::cast_possible_truncation)]
-        0x70..=0x87 => KeyEvent::F((event.virtual_key_code - 0x6F) as u8), // F1-F24
         _ => {
-            let c = char::from_u32(u32::from(unsafe { event.u_char.unicode_char })).unwrap_or(' ');
-            if ctrl && c.is_ascii_alphabetic() {
-                KeyEvent::Ctrl(c)
-            } else {
-                KeyEvent::Char(c)
+            // AltGr is reported as Ctrl + Alt, but the character it types is what was meant
+            if unicode_char >= 0x20 && modifiers.contains(Modifiers::CTRL | Modifiers::ALT) {
+                modifiers.remove(Modifiers::CTRL | Modifiers::ALT);
+            }
+            // The character is already the shifted one
+            if unicode_char >= 0x20 {
+                modifiers.remove(Modifiers::SHIFT);
             }
+            let c = char::from_u32(u32::from(unicode_char)).unwrap_or(' ');
+            KeyCode::Char(c)
         }
-    }
+    };
+    KeyEvent::new(code, modifiers)
 }
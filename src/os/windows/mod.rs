@@ -1,5 +1,9 @@
-use std::io;
+use super::{RawModeOptions, TerminalSize, panic_ends_process};
+use crate::ansi::{CURSOR_SHOW, restore_active_modes};
+use std::io::{self, Write};
 use std::os::windows::raw::HANDLE;
+use std::panic;
+use std::sync::{Mutex, Once, PoisonError, TryLockError};
 
 #[link(name = "kernel32")]
 unsafe extern "system" {
@@ -25,7 +29,12 @@ const INVALID_HANDLE_VALUE: HANDLE = -1isize as HANDLE;
 struct ConsoleScreenBufferInfo {
     x: u16,
     y: u16,
-    _unused: [u16; 9],
+    cursor_x: u16,
+    cursor_y: u16,
+    _attributes: u16,
+    window_left: u16,
+    window_top: u16,
+    _unused: [u16; 4],
 }
 
 fn get_stdin_handle() -> io::Result<HANDLE> {
@@ -62,6 +71,9 @@ fn get_console_mode(handle: HANDLE, mode: &mut u32) -> io::Result<()> {
     }
 }
 
+/// The console mode from before raw mode was enabled, `None` while it isn't enabled
+static ORIGINAL_MODE: Mutex<Option<u32>> = Mutex::new(None);
+
 /// Enables raw mode, which disables line buffering, input echoing, and output canonicalization
 ///
 /// # Errors
@@ -70,15 +82,40 @@ fn get_console_mode(handle: HANDLE, mode: &mut u32) -> io::Result<()> {
 /// stdin is not a tty,
 /// or it fails to change terminal settings
 pub fn enable_raw_mode() -> io::Result<()> {
+    enable_raw_mode_with(RawModeOptions::raw())
+}
+
+/// Enables raw mode with the settings in {options}, see `RawModeOptions`
+///
+/// If raw mode is already enabled the settings are replaced
+///
+/// # Errors
+///
+/// If there is no stdin,
+/// stdin is not a tty,
+/// or it fails to change terminal settings
+pub fn enable_raw_mode_with(options: RawModeOptions) -> io::Result<()> {
     let handle = get_stdin_handle()?;
-    let mut mode = 0;
-    get_console_mode(handle, &mut mode)?;
-    mode &= !(ENABLE_ECHO_INPUT | ENABLE_LINE_INPUT | ENABLE_PROCESSED_INPUT);
+    let mut original = ORIGINAL_MODE.lock().unwrap_or_else(PoisonError::into_inner);
+    // Settings are always based on the original ones so enabling again replaces them
+    let mut mode = if let Some(mode) = *original {
+        mode
+    } else {
+        let mut mode = 0;
+        get_console_mode(handle, &mut mode)?;
+        mode
+    };
+    let restore = mode;
+    mode &= !(ENABLE_ECHO_INPUT | ENABLE_LINE_INPUT);
+    if !options.keep_signals {
+        mode &= !ENABLE_PROCESSED_INPUT;
+    }
     set_console_mode(handle, mode)?;
+    *original = Some(restore);
     Ok(())
 }
 
-/// Disables raw mode, which enables line buffering, input echoing, and output canonicalization
+/// Disables raw mode, restoring the terminal settings from before it was enabled
 ///
 /// # Errors
 ///
@@ -86,14 +123,84 @@ pub fn enable_raw_mode() -> io::Result<()> {
 /// stdin is not a tty,
 /// or it fails to change terminal settings
 pub fn disable_raw_mode() -> io::Result<()> {
-    let handle = get_stdin_handle()?;
-    let mut mode = 0;
-    get_console_mode(handle, &mut mode)?;
-    mode |= ENABLE_ECHO_INPUT | ENABLE_LINE_INPUT | ENABLE_PROCESSED_INPUT;
-    set_console_mode(handle, mode)?;
+    let mut original = ORIGINAL_MODE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(mode) = *original {
+        set_console_mode(get_stdin_handle()?, mode)?;
+        *original = None;
+    }
     Ok(())
 }
 
+/// Restores the terminal if the process panics
+///
+/// The handlers in `ansi` and `RawModeHandler` only restore the terminal when they are dropped,
+/// which doesn't happen with `panic = "abort"`. Once this is called the modes enabled by the
+/// handlers are disabled, the cursor is shown, and raw mode is disabled before the panic message
+/// is printed. Dropping the handlers afterwards does nothing
+///
+/// Only panics that end the process restore the terminal: any panic with `panic = "abort"`,
+/// otherwise panics in the main thread. Panics in other threads, which can be caught by
+/// `JoinHandle::join`, leave the terminal alone. Catching a panic in the main thread with
+/// `catch_unwind` still restores the terminal
+///
+/// On Unix it also restores the terminal on `SIGTERM` and `SIGHUP`, calling it again does nothing
+///
+/// # Errors
+///
+/// Never on Windows
+pub fn install_restore_hook() -> io::Result<()> {
+    static PANIC_HOOK: Once = Once::new();
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if panic_ends_process() {
+                restore_terminal();
+            }
+            previous(info);
+        }));
+    });
+    Ok(())
+}
+
+/// Restores the console, see `install_restore_hook`
+fn restore_terminal() {
+    // Nothing can be done about errors here
+    let mut stdout = io::stdout();
+    restore_active_modes(|code| {
+        let _ = stdout.write_all(code.as_bytes());
+    });
+    let _ = stdout.write_all(CURSOR_SHOW.as_bytes());
+    let _ = stdout.flush();
+
+    let mut original = match ORIGINAL_MODE.try_lock() {
+        Ok(original) => original,
+        Err(TryLockError::Poisoned(error)) => error.into_inner(),
+        Err(TryLockError::WouldBlock) => return,
+    };
+    if let (Some(mode), Ok(handle)) = (*original, get_stdin_handle()) {
+        let _ = set_console_mode(handle, mode);
+        *original = None;
+    }
+}
+
+/// Suspends the process like Ctrl + Z does on Unix
+///
+/// # Errors
+///
+/// Always on Windows, as there is no job control
+pub fn suspend() -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Handles being stopped and continued while raw mode is enabled
+///
+/// # Errors
+///
+/// Always on Windows, as there is no job control
+pub fn enable_job_control() -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
 /// Enables ANSI support on Windows terminals
 ///
 /// ANSI is on by default on *nix machines but still exists on them for simpler usage
@@ -134,4 +241,97 @@ pub fn get_terminal_size() -> io::Result<(u16, u16)> {
     Err(io::Error::last_os_error())
 }
 
+/// Gets the size of the terminal in cells and pixels
+///
+/// The size in pixels is always 0 on Windows
+///
+/// # Errors
+///
+/// If there is no stdout,
+/// if stdout isn't a TTY, or
+/// if it fails to retrieve the terminal size
+pub fn get_terminal_size_detailed() -> io::Result<TerminalSize> {
+    let (columns, rows) = get_terminal_size()?;
+    Ok(TerminalSize {
+        columns,
+        rows,
+        ..TerminalSize::default()
+    })
+}
+
+/// Gets the position of the cursor
+///
+/// Returns in (column, row) format, origin is 0, 0
+///
+/// # Errors
+///
+/// If there is no stdout,
+/// if stdout isn't a TTY, or
+/// if it fails to retrieve the cursor position
+pub fn get_cursor_position() -> io::Result<(u16, u16)> {
+    let handle = get_stdout_handle()?;
+    let mut csbi = ConsoleScreenBufferInfo::default();
+    if unsafe { GetConsoleScreenBufferInfo(handle, &mut csbi) != 0 } {
+        // The position is in the screen buffer, make it relative to the visible window
+        let column = csbi.cursor_x.saturating_sub(csbi.window_left);
+        let row = csbi.cursor_y.saturating_sub(csbi.window_top);
+        return Ok((column, row));
+    }
+    Err(io::Error::last_os_error())
+}
+
+/// Asks the terminal what it supports
+///
+/// # Errors
+///
+/// Always on Windows, as the terminal's replies can't be read from the console input
+pub fn probe_capabilities() -> io::Result<crate::ansi::Capabilities> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Gets the terminal's default foreground color
+///
+/// # Errors
+///
+/// Always on Windows, as the terminal's replies can't be read from the console input
+pub fn get_foreground_color() -> io::Result<(u8, u8, u8)> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Gets the terminal's default background color
+///
+/// # Errors
+///
+/// Always on Windows, as the terminal's replies can't be read from the console input
+pub fn get_background_color() -> io::Result<(u8, u8, u8)> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Gets color {index} of the terminal's 256 color palette
+///
+/// # Errors
+///
+/// Always on Windows, as the terminal's replies can't be read from the console input
+pub fn get_palette_color(_index: u8) -> io::Result<(u8, u8, u8)> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Gets if the terminal's background is dark
+///
+/// # Errors
+///
+/// Always on Windows, as the terminal's replies can't be read from the console input
+pub fn is_dark_background() -> io::Result<bool> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Gets the contents of {selection} through the terminal
+///
+/// # Errors
+///
+/// Always on Windows, as the terminal's replies can't be read from the console input
+pub fn get_clipboard(_selection: crate::ansi::ClipboardSelection) -> io::Result<String> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
 pub mod input;